@@ -0,0 +1,214 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tauri::Manager;
+
+use crate::credentials;
+use crate::history::now_millis;
+
+const ACCOUNTS_FILE: &str = "accounts.json";
+
+/// A GitHub account whose token is kept in the credential store.
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) struct Account {
+    pub(crate) login: String,
+    /// Unix timestamp in milliseconds.
+    pub(crate) added_at: u64,
+}
+
+/// Account metadata persisted as JSON in the app config dir. Tokens never
+/// appear here.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+struct AccountList {
+    active: Option<String>,
+    accounts: Vec<Account>,
+}
+
+pub(crate) struct AccountRegistry(Mutex<AccountList>);
+
+impl AccountRegistry {
+    pub(crate) fn load(app: &tauri::AppHandle) -> Self {
+        let list = accounts_path(app)
+            .ok()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default();
+        AccountRegistry(Mutex::new(list))
+    }
+}
+
+#[derive(Serialize)]
+pub(crate) struct AccountSummary {
+    pub(crate) login: String,
+    pub(crate) added_at: u64,
+    pub(crate) active: bool,
+}
+
+fn accounts_path(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    let dir = app
+        .path()
+        .app_config_dir()
+        .map_err(|err| format!("Failed to resolve config dir: {err}"))?;
+    Ok(dir.join(ACCOUNTS_FILE))
+}
+
+fn write_list(app: &tauri::AppHandle, list: &AccountList) -> Result<(), String> {
+    let path = accounts_path(app)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|err| format!("Failed to create config dir: {err}"))?;
+    }
+    let contents = serde_json::to_string_pretty(list)
+        .map_err(|err| format!("Failed to serialize accounts: {err}"))?;
+    fs::write(&path, contents).map_err(|err| format!("Failed to write accounts: {err}"))
+}
+
+fn token_key(login: &str) -> String {
+    format!("{}:{login}", credentials::GITHUB_TOKEN_KEY)
+}
+
+/// Applies `update` to the account list and persists the result.
+fn modify<T>(
+    app: &tauri::AppHandle,
+    update: impl FnOnce(&mut AccountList) -> Result<T, String>,
+) -> Result<T, String> {
+    let registry = app.state::<AccountRegistry>();
+    let mut list = registry
+        .0
+        .lock()
+        .map_err(|_| "Account list is poisoned".to_string())?;
+    let value = update(&mut list)?;
+    write_list(app, &list)?;
+    Ok(value)
+}
+
+/// Stores `token` for `login`, replacing any earlier token for the same
+/// account, and makes it the active account. Returns the credential store's
+/// name for status messages.
+pub(crate) fn add_account(
+    app: &tauri::AppHandle,
+    login: &str,
+    token: &str,
+) -> Result<&'static str, String> {
+    let store = credentials::active_store(app)?;
+    store.save(&token_key(login), token)?;
+    // The single token kept by earlier versions is superseded by accounts.
+    store.delete(credentials::GITHUB_TOKEN_KEY)?;
+    modify(app, |list| {
+        if !list.accounts.iter().any(|account| account.login == login) {
+            list.accounts.push(Account {
+                login: login.to_string(),
+                added_at: now_millis(),
+            });
+        }
+        list.active = Some(login.to_string());
+        Ok(())
+    })?;
+    Ok(store.name())
+}
+
+/// Forgets `login` and deletes its token. The next remaining account becomes
+/// active if `login` was.
+pub(crate) fn remove_account(app: &tauri::AppHandle, login: &str) -> Result<(), String> {
+    credentials::active_store(app)?.delete(&token_key(login))?;
+    modify(app, |list| {
+        list.accounts.retain(|account| account.login != login);
+        if list.active.as_deref() == Some(login) {
+            list.active = list.accounts.first().map(|account| account.login.clone());
+        }
+        Ok(())
+    })
+}
+
+pub(crate) fn active_login(app: &tauri::AppHandle) -> Option<String> {
+    app.state::<AccountRegistry>()
+        .0
+        .lock()
+        .ok()
+        .and_then(|list| list.active.clone())
+}
+
+/// Credential store keys of every account's token.
+fn token_keys(app: &tauri::AppHandle) -> Vec<String> {
+    app.state::<AccountRegistry>()
+        .0
+        .lock()
+        .map(|list| {
+            list.accounts
+                .iter()
+                .map(|account| token_key(&account.login))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Credential store keys of every secret ghc keeps: each account's token
+/// plus the single token stored by earlier versions.
+pub(crate) fn secret_keys(app: &tauri::AppHandle) -> Vec<String> {
+    let mut keys = token_keys(app);
+    keys.push(credentials::GITHUB_TOKEN_KEY.to_string());
+    keys
+}
+
+/// Looks up the token for `login`, or for the active account when `login` is
+/// `None`. Without any accounts, falls back to the single token stored by
+/// earlier versions.
+pub(crate) fn token_for(
+    app: &tauri::AppHandle,
+    login: Option<&str>,
+) -> Result<Option<String>, String> {
+    let store = credentials::active_store(app)?;
+    let login = match login {
+        Some(login) => {
+            let known = app
+                .state::<AccountRegistry>()
+                .0
+                .lock()
+                .map(|list| list.accounts.iter().any(|account| account.login == login))
+                .unwrap_or(false);
+            if !known {
+                return Err(format!("Unknown GitHub account: {login}"));
+            }
+            login.to_string()
+        }
+        None => match active_login(app) {
+            Some(login) => login,
+            None => return store.load(credentials::GITHUB_TOKEN_KEY),
+        },
+    };
+    Ok(store
+        .load(&token_key(&login))?
+        .map(|token| token.trim().to_string())
+        .filter(|token| !token.is_empty()))
+}
+
+#[tauri::command]
+pub(crate) fn list_accounts(app: tauri::AppHandle) -> Result<Vec<AccountSummary>, String> {
+    let registry = app.state::<AccountRegistry>();
+    let list = registry
+        .0
+        .lock()
+        .map_err(|_| "Account list is poisoned".to_string())?;
+    Ok(list
+        .accounts
+        .iter()
+        .map(|account| AccountSummary {
+            login: account.login.clone(),
+            added_at: account.added_at,
+            active: list.active.as_deref() == Some(account.login.as_str()),
+        })
+        .collect())
+}
+
+/// Makes `login` the account used when a run does not name one.
+#[tauri::command]
+pub(crate) fn switch_account(app: tauri::AppHandle, login: String) -> Result<(), String> {
+    modify(&app, |list| {
+        if !list.accounts.iter().any(|account| account.login == login) {
+            return Err(format!("Unknown GitHub account: {login}"));
+        }
+        list.active = Some(login);
+        Ok(())
+    })
+}
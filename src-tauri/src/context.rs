@@ -0,0 +1,682 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::env;
+use std::fs;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Prefix shared by every staging directory so leftovers can be swept on exit.
+pub(crate) const STAGING_PREFIX: &str = ".copilot-context-";
+
+/// Directory names that are never copied when a folder is attached.
+const SKIPPED_DIRS: [&str; 2] = [".git", "node_modules"];
+
+/// How many leading bytes are sniffed for NUL bytes and UTF-8 validity.
+const SNIFF_BYTES: usize = 8 * 1024;
+
+/// Rough characters-per-token ratio used for estimates.
+const BYTES_PER_TOKEN: u64 = 4;
+
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum OversizeAction {
+    /// Abort the run.
+    Refuse,
+    /// Keep the head and tail of the file with a marker in between.
+    Truncate,
+    /// Send the file as-is and report a warning.
+    Warn,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum BinaryAction {
+    Refuse,
+    Skip,
+    Warn,
+}
+
+/// Limits applied to attached context before it is staged.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct ContextPolicy {
+    pub(crate) max_file_bytes: u64,
+    /// Limit for all attachments together. Exceeding it refuses the run when
+    /// `on_oversize` is `refuse` and only warns otherwise.
+    pub(crate) max_total_bytes: u64,
+    pub(crate) on_oversize: OversizeAction,
+    pub(crate) on_binary: BinaryAction,
+    pub(crate) truncate_head_bytes: u64,
+    pub(crate) truncate_tail_bytes: u64,
+}
+
+impl Default for ContextPolicy {
+    fn default() -> Self {
+        ContextPolicy {
+            max_file_bytes: 512 * 1024,
+            max_total_bytes: 2 * 1024 * 1024,
+            on_oversize: OversizeAction::Truncate,
+            on_binary: BinaryAction::Skip,
+            truncate_head_bytes: 192 * 1024,
+            truncate_tail_bytes: 64 * 1024,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum ContentKind {
+    Text,
+    /// No NUL bytes, but not valid UTF-8 either (e.g. Latin-1).
+    NonUtf8,
+    Binary,
+}
+
+#[derive(Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum ContextDecision {
+    Included,
+    Truncated,
+    Skipped,
+    Refused,
+}
+
+#[derive(Clone, Serialize)]
+pub(crate) struct ContextFileReport {
+    pub(crate) path: String,
+    pub(crate) size_bytes: u64,
+    pub(crate) kind: ContentKind,
+    pub(crate) estimated_tokens: Option<u64>,
+    pub(crate) decision: ContextDecision,
+    pub(crate) warnings: Vec<String>,
+}
+
+/// Outcome of the pre-flight inspection, returned to the UI.
+#[derive(Clone, Default, Serialize)]
+pub(crate) struct ContextReport {
+    pub(crate) files: Vec<ContextFileReport>,
+    /// Bytes that will actually be sent after truncation and skips.
+    pub(crate) total_bytes: u64,
+    pub(crate) estimated_tokens: u64,
+    pub(crate) warnings: Vec<String>,
+    /// Set when the policy rejects the attachments.
+    pub(crate) refused: Option<String>,
+}
+
+/// A file found under an attached path and where it goes in staging.
+struct PlannedFile {
+    source: PathBuf,
+    relative: PathBuf,
+}
+
+struct Plan {
+    files: Vec<PlannedFile>,
+    /// Top-level names, one per attached path, and whether it is a directory.
+    entries: Vec<(PathBuf, bool)>,
+}
+
+/// Context files copied into a per-request staging directory.
+pub(crate) struct StagedContext {
+    pub(crate) root: PathBuf,
+    /// Top-level staged entries, one per attached path.
+    pub(crate) entries: Vec<PathBuf>,
+    pub(crate) report: ContextReport,
+}
+
+impl StagedContext {
+    pub(crate) fn cleanup(&self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
+
+/// Shared parent of the staging directories, named by the `temp_dir_name`
+/// setting.
+pub(crate) fn temp_root(name: &str) -> PathBuf {
+    env::temp_dir().join(name.trim())
+}
+
+fn clean_sources(paths: &[String]) -> Vec<PathBuf> {
+    paths
+        .iter()
+        .map(|path| path.trim())
+        .filter(|path| !path.is_empty())
+        .map(PathBuf::from)
+        .collect()
+}
+
+/// Inspects `paths` against `policy` without copying anything.
+pub(crate) fn inspect_context(
+    paths: &[String],
+    policy: &ContextPolicy,
+) -> Result<ContextReport, String> {
+    let plan = plan_sources(&clean_sources(paths))?;
+    Ok(inspect_plan(&plan, policy))
+}
+
+/// Copies every file or directory in `paths` into a fresh staging directory
+/// under `temp_root`, preserving the structure below each attached directory and applying
+/// `policy` to each file.
+///
+/// Returns `None` when no usable path was given, and an error carrying the
+/// report when the policy refuses the attachments.
+pub(crate) fn stage_context(
+    request_id: &str,
+    paths: &[String],
+    policy: &ContextPolicy,
+    temp_root: &Path,
+) -> Result<Option<StagedContext>, StageError> {
+    let sources = clean_sources(paths);
+    if sources.is_empty() {
+        return Ok(None);
+    }
+    let plan = plan_sources(&sources)?;
+    let report = inspect_plan(&plan, policy);
+    if report.refused.is_some() {
+        return Err(StageError::Refused(report));
+    }
+
+    let stamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|err| format!("Failed to generate temp name: {err}"))?
+        .as_millis();
+    let request_tag: String = request_id
+        .chars()
+        .filter(|ch| ch.is_ascii_alphanumeric() || *ch == '-')
+        .collect();
+    let root = temp_root.join(format!("{STAGING_PREFIX}{stamp}-{request_tag}"));
+    fs::create_dir_all(&root).map_err(|err| format!("Failed to create temp dir: {err}"))?;
+
+    let mut staged = StagedContext {
+        root,
+        entries: Vec::new(),
+        report,
+    };
+    if let Err(err) = write_plan(&staged.root, &plan, &staged.report, policy) {
+        staged.cleanup();
+        return Err(err.into());
+    }
+    for (entry, is_dir) in &plan.entries {
+        let target = staged.root.join(entry);
+        // Attached directories whose files were all skipped still need to
+        // exist, while skipped single files are left out of the prompt.
+        if *is_dir {
+            let _ = fs::create_dir_all(&target);
+        } else if !target.exists() {
+            continue;
+        }
+        staged.entries.push(target);
+    }
+    Ok(Some(staged))
+}
+
+pub(crate) enum StageError {
+    Refused(ContextReport),
+    Failed(String),
+}
+
+impl From<String> for StageError {
+    fn from(message: String) -> Self {
+        StageError::Failed(message)
+    }
+}
+
+fn plan_sources(sources: &[PathBuf]) -> Result<Plan, String> {
+    let mut used_names = HashSet::new();
+    let mut plan = Plan {
+        files: Vec::new(),
+        entries: Vec::new(),
+    };
+    for source in sources {
+        let name = source
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or("context");
+        let entry = PathBuf::from(unique_name(name, &mut used_names));
+        let is_dir = source.is_dir();
+        if is_dir {
+            plan_dir(source, &entry, &mut plan.files)?;
+        } else if source.is_file() {
+            plan.files.push(PlannedFile {
+                source: source.clone(),
+                relative: entry.clone(),
+            });
+        } else {
+            return Err(format!("Context path not found: {}", source.display()));
+        }
+        plan.entries.push((entry, is_dir));
+    }
+    Ok(plan)
+}
+
+/// Keeps two attachments with the same file name from overwriting each other
+/// by numbering duplicates before their extension (`a.txt`, `a-2.txt`).
+fn unique_name(name: &str, used: &mut HashSet<String>) -> String {
+    let path = Path::new(name);
+    let stem = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or(name);
+    let extension = path.extension().and_then(|ext| ext.to_str());
+    let mut candidate = name.to_string();
+    let mut index = 2;
+    while !used.insert(candidate.clone()) {
+        candidate = match extension {
+            Some(ext) => format!("{stem}-{index}.{ext}"),
+            None => format!("{stem}-{index}"),
+        };
+        index += 1;
+    }
+    candidate
+}
+
+fn plan_dir(source: &Path, relative: &Path, files: &mut Vec<PlannedFile>) -> Result<(), String> {
+    let entries = fs::read_dir(source)
+        .map_err(|err| format!("Failed to read directory {}: {err}", source.display()))?;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let name = entry.file_name();
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        if file_type.is_dir() {
+            if SKIPPED_DIRS.iter().any(|skipped| name == *skipped) {
+                continue;
+            }
+            plan_dir(&path, &relative.join(&name), files)?;
+        } else if file_type.is_file() {
+            files.push(PlannedFile {
+                source: path,
+                relative: relative.join(&name),
+            });
+        }
+    }
+    Ok(())
+}
+
+fn inspect_plan(plan: &Plan, policy: &ContextPolicy) -> ContextReport {
+    let mut report = ContextReport::default();
+    for file in &plan.files {
+        let inspected = inspect_file(&file.source, policy);
+        report.total_bytes += sent_bytes(&inspected, policy);
+        report.estimated_tokens += inspected.estimated_tokens.unwrap_or(0);
+        if inspected.decision == ContextDecision::Refused && report.refused.is_none() {
+            report.refused = inspected.warnings.first().cloned();
+        }
+        report.files.push(inspected);
+    }
+    if report.total_bytes > policy.max_total_bytes {
+        let message = format!(
+            "Attached context is {} bytes, above the {} byte limit.",
+            report.total_bytes, policy.max_total_bytes
+        );
+        if policy.on_oversize == OversizeAction::Refuse {
+            report.refused.get_or_insert(message);
+        } else {
+            report.warnings.push(message);
+        }
+    }
+    report
+}
+
+fn sent_bytes(file: &ContextFileReport, policy: &ContextPolicy) -> u64 {
+    match file.decision {
+        ContextDecision::Included => file.size_bytes,
+        ContextDecision::Truncated => file.size_bytes.min(
+            policy
+                .truncate_head_bytes
+                .saturating_add(policy.truncate_tail_bytes),
+        ),
+        ContextDecision::Skipped | ContextDecision::Refused => 0,
+    }
+}
+
+fn inspect_file(path: &Path, policy: &ContextPolicy) -> ContextFileReport {
+    let display = path.display().to_string();
+    let size_bytes = fs::metadata(path).map(|meta| meta.len()).unwrap_or(0);
+    let kind = sniff_kind(path);
+    let mut report = ContextFileReport {
+        path: display.clone(),
+        size_bytes,
+        kind,
+        estimated_tokens: None,
+        decision: ContextDecision::Included,
+        warnings: Vec::new(),
+    };
+
+    if kind == ContentKind::Binary {
+        match policy.on_binary {
+            BinaryAction::Refuse => {
+                report.decision = ContextDecision::Refused;
+                report
+                    .warnings
+                    .push(format!("{display} looks like a binary file."));
+            }
+            BinaryAction::Skip => {
+                report.decision = ContextDecision::Skipped;
+                report.warnings.push(format!(
+                    "{display} looks like a binary file and was skipped."
+                ));
+            }
+            BinaryAction::Warn => {
+                report.warnings.push(format!(
+                    "{display} looks like a binary file; Copilot may not read it."
+                ));
+            }
+        }
+        return report;
+    }
+
+    if kind == ContentKind::NonUtf8 {
+        report
+            .warnings
+            .push(format!("{display} is not valid UTF-8."));
+    }
+
+    if size_bytes > policy.max_file_bytes {
+        match policy.on_oversize {
+            OversizeAction::Refuse => {
+                report.decision = ContextDecision::Refused;
+                report.warnings.push(format!(
+                    "{display} is {size_bytes} bytes, above the {} byte limit.",
+                    policy.max_file_bytes
+                ));
+            }
+            OversizeAction::Truncate
+                if size_bytes
+                    > policy
+                        .truncate_head_bytes
+                        .saturating_add(policy.truncate_tail_bytes) =>
+            {
+                report.decision = ContextDecision::Truncated;
+                report.warnings.push(format!(
+                    "{display} is {size_bytes} bytes and was truncated to its first {} and last {} bytes.",
+                    policy.truncate_head_bytes, policy.truncate_tail_bytes
+                ));
+            }
+            _ => {
+                report.warnings.push(format!(
+                    "{display} is {size_bytes} bytes, above the {} byte limit.",
+                    policy.max_file_bytes
+                ));
+            }
+        }
+    }
+
+    report.estimated_tokens = Some(sent_bytes(&report, policy).div_ceil(BYTES_PER_TOKEN));
+    report
+}
+
+/// Classifies a file from its first [`SNIFF_BYTES`].
+fn sniff_kind(path: &Path) -> ContentKind {
+    let mut sample = Vec::with_capacity(SNIFF_BYTES);
+    if let Ok(file) = fs::File::open(path) {
+        let _ = file.take(SNIFF_BYTES as u64).read_to_end(&mut sample);
+    }
+    if sample.contains(&0) {
+        return ContentKind::Binary;
+    }
+    match std::str::from_utf8(&sample) {
+        Ok(_) => ContentKind::Text,
+        // The sample may end in the middle of a multi-byte character.
+        Err(err) if err.error_len().is_none() => ContentKind::Text,
+        Err(_) => ContentKind::NonUtf8,
+    }
+}
+
+fn write_plan(
+    root: &Path,
+    plan: &Plan,
+    report: &ContextReport,
+    policy: &ContextPolicy,
+) -> Result<(), String> {
+    for (file, inspected) in plan.files.iter().zip(&report.files) {
+        let target = root.join(&file.relative);
+        match inspected.decision {
+            ContextDecision::Skipped | ContextDecision::Refused => continue,
+            _ => {}
+        }
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|err| format!("Failed to create {}: {err}", parent.display()))?;
+        }
+        if inspected.decision == ContextDecision::Truncated {
+            write_truncated(&file.source, &target, inspected.size_bytes, policy)?;
+        } else {
+            fs::copy(&file.source, &target).map_err(|err| {
+                format!(
+                    "Failed to copy context file {}: {err}",
+                    file.source.display()
+                )
+            })?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes the head and tail of `source` to `target` with a marker noting how
+/// much was left out.
+fn write_truncated(
+    source: &Path,
+    target: &Path,
+    size_bytes: u64,
+    policy: &ContextPolicy,
+) -> Result<(), String> {
+    let read_err = |err: std::io::Error| format!("Failed to read {}: {err}", source.display());
+    let mut file = fs::File::open(source).map_err(read_err)?;
+
+    let mut head = Vec::new();
+    (&mut file)
+        .take(policy.truncate_head_bytes)
+        .read_to_end(&mut head)
+        .map_err(read_err)?;
+    let head_len = utf8_prefix_len(&head);
+    head.truncate(head_len);
+
+    // The file may have grown since it was inspected; anything appended
+    // after that is left out rather than staged in full.
+    let mut tail = Vec::new();
+    file.seek(SeekFrom::Start(
+        size_bytes.saturating_sub(policy.truncate_tail_bytes),
+    ))
+    .map_err(read_err)?;
+    file.take(policy.truncate_tail_bytes)
+        .read_to_end(&mut tail)
+        .map_err(read_err)?;
+    let tail_start = tail
+        .iter()
+        .position(|byte| byte & 0b1100_0000 != 0b1000_0000)
+        .unwrap_or(tail.len());
+    let tail = &tail[tail_start..];
+
+    let omitted = size_bytes
+        .saturating_sub(head.len() as u64)
+        .saturating_sub(tail.len() as u64);
+    let mut contents = head;
+    contents.extend_from_slice(
+        format!("\n\n[... ghc truncated {omitted} bytes from the middle of this file ...]\n\n")
+            .as_bytes(),
+    );
+    contents.extend_from_slice(tail);
+    fs::write(target, contents)
+        .map_err(|err| format!("Failed to write {}: {err}", target.display()))
+}
+
+/// Length of the longest prefix that does not end inside a UTF-8 character.
+fn utf8_prefix_len(bytes: &[u8]) -> usize {
+    match std::str::from_utf8(bytes) {
+        Ok(_) => bytes.len(),
+        Err(err) if err.error_len().is_none() => err.valid_up_to(),
+        Err(_) => bytes.len(),
+    }
+}
+
+/// Removes staging directories (and legacy single-file copies) left behind
+/// by earlier runs under `temp_root`.
+pub(crate) fn sweep_staging(temp_root: &Path) {
+    let Ok(entries) = fs::read_dir(temp_root) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
+            if name.starts_with(STAGING_PREFIX) {
+                if path.is_dir() {
+                    let _ = fs::remove_dir_all(path);
+                } else {
+                    let _ = fs::remove_file(path);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A scratch directory removed when dropped.
+    struct Scratch(PathBuf);
+
+    impl Scratch {
+        fn new() -> Self {
+            let dir = env::temp_dir().join(format!("ghc-context-test-{}", uuid::Uuid::new_v4()));
+            fs::create_dir_all(&dir).unwrap();
+            Scratch(dir)
+        }
+
+        fn file(&self, name: &str, contents: &[u8]) -> PathBuf {
+            let path = self.0.join(name);
+            fs::write(&path, contents).unwrap();
+            path
+        }
+    }
+
+    impl Drop for Scratch {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn policy(head: u64, tail: u64) -> ContextPolicy {
+        ContextPolicy {
+            max_file_bytes: head + tail,
+            max_total_bytes: 1024,
+            on_oversize: OversizeAction::Truncate,
+            on_binary: BinaryAction::Skip,
+            truncate_head_bytes: head,
+            truncate_tail_bytes: tail,
+        }
+    }
+
+    fn marker(omitted: u64) -> String {
+        format!("\n\n[... ghc truncated {omitted} bytes from the middle of this file ...]\n\n")
+    }
+
+    #[test]
+    fn unique_name_numbers_duplicates_before_the_extension() {
+        let mut used = HashSet::new();
+        assert_eq!(unique_name("a.txt", &mut used), "a.txt");
+        assert_eq!(unique_name("a.txt", &mut used), "a-2.txt");
+        assert_eq!(unique_name("a.txt", &mut used), "a-3.txt");
+        assert_eq!(unique_name("Makefile", &mut used), "Makefile");
+        assert_eq!(unique_name("Makefile", &mut used), "Makefile-2");
+        assert_eq!(unique_name("archive.tar.gz", &mut used), "archive.tar.gz");
+        assert_eq!(unique_name("archive.tar.gz", &mut used), "archive.tar-2.gz");
+    }
+
+    #[test]
+    fn utf8_prefix_len_stops_before_a_split_character() {
+        let text = "aé".as_bytes();
+        assert_eq!(utf8_prefix_len(text), 3);
+        assert_eq!(utf8_prefix_len(&text[..2]), 1);
+        // Invalid bytes are not a split character; keep everything.
+        assert_eq!(utf8_prefix_len(b"a\xffb"), 3);
+    }
+
+    #[test]
+    fn truncation_splits_on_utf8_boundaries_and_counts_omitted_bytes() {
+        let scratch = Scratch::new();
+        // Two bytes per Greek letter, so five-byte cuts land mid-character.
+        let contents = format!("αβγδε{}ζηθικ", "-".repeat(10));
+        let source = scratch.file("log.txt", contents.as_bytes());
+        let target = scratch.0.join("staged.txt");
+
+        write_truncated(&source, &target, contents.len() as u64, &policy(5, 5)).unwrap();
+
+        let staged = fs::read_to_string(&target).unwrap();
+        assert_eq!(staged, format!("αβ{}ικ", marker(22)));
+    }
+
+    #[test]
+    fn truncation_ignores_bytes_appended_after_inspection() {
+        let scratch = Scratch::new();
+        let source = scratch.file(
+            "live.log",
+            format!("{}{}", "a".repeat(20), "b".repeat(1000)).as_bytes(),
+        );
+        let target = scratch.0.join("staged.log");
+
+        // Inspected while the file held only the 20 `a`s.
+        write_truncated(&source, &target, 20, &policy(5, 5)).unwrap();
+        assert_eq!(
+            fs::read_to_string(&target).unwrap(),
+            format!("aaaaa{}aaaaa", marker(10))
+        );
+
+        // A file that shrank below head + tail must not underflow the count.
+        write_truncated(&source, &target, 6, &policy(5, 5)).unwrap();
+        assert!(fs::read_to_string(&target).unwrap().contains(&marker(0)));
+    }
+
+    #[test]
+    fn binary_files_follow_the_binary_policy() {
+        let scratch = Scratch::new();
+        let binary = scratch.file("image.bin", b"\x89PNG\0\0\0data");
+
+        let mut policy = policy(512, 512);
+        let skipped = inspect_file(&binary, &policy);
+        assert!(skipped.kind == ContentKind::Binary);
+        assert!(skipped.decision == ContextDecision::Skipped);
+        assert_eq!(sent_bytes(&skipped, &policy), 0);
+
+        policy.on_binary = BinaryAction::Refuse;
+        assert!(inspect_file(&binary, &policy).decision == ContextDecision::Refused);
+
+        policy.on_binary = BinaryAction::Warn;
+        let warned = inspect_file(&binary, &policy);
+        assert!(warned.decision == ContextDecision::Included);
+        assert_eq!(warned.warnings.len(), 1);
+    }
+
+    #[test]
+    fn oversized_files_are_truncated_or_refused() {
+        let scratch = Scratch::new();
+        let large = scratch.file("large.txt", "x".repeat(100).as_bytes());
+        let small = scratch.file("small.txt", b"fits");
+
+        let mut policy = policy(10, 10);
+        let truncated = inspect_file(&large, &policy);
+        assert!(truncated.decision == ContextDecision::Truncated);
+        assert_eq!(sent_bytes(&truncated, &policy), 20);
+        assert_eq!(truncated.estimated_tokens, Some(5));
+        assert!(inspect_file(&small, &policy).decision == ContextDecision::Included);
+
+        policy.on_oversize = OversizeAction::Refuse;
+        assert!(inspect_file(&large, &policy).decision == ContextDecision::Refused);
+
+        policy.on_oversize = OversizeAction::Warn;
+        let warned = inspect_file(&large, &policy);
+        assert!(warned.decision == ContextDecision::Included);
+        assert_eq!(sent_bytes(&warned, &policy), 100);
+    }
+
+    #[test]
+    fn latin1_text_is_flagged_but_included() {
+        let scratch = Scratch::new();
+        let latin1 = scratch.file("latin1.txt", b"caf\xe9 au lait");
+        let report = inspect_file(&latin1, &policy(512, 512));
+        assert!(report.kind == ContentKind::NonUtf8);
+        assert!(report.decision == ContextDecision::Included);
+    }
+}
@@ -0,0 +1,110 @@
+use tauri::Manager;
+
+use crate::history::{self, Conversation, HistoryStore, Turn, TurnStatus};
+
+/// Limits on how much of a conversation is replayed into the next prompt.
+pub(crate) struct TurnBudget {
+    pub(crate) max_turns: usize,
+    pub(crate) max_chars: usize,
+}
+
+/// The prompt sent to Copilot for a follow-up, plus how many earlier turns it
+/// carries.
+pub(crate) struct AssembledPrompt {
+    pub(crate) prompt: String,
+    pub(crate) prior_turns: usize,
+}
+
+/// Prefixes `prompt` with the most recent successful turns of
+/// `conversation_id` that fit in `budget`.
+///
+/// The Copilot CLI runs every `-p` call as a fresh session, so earlier turns
+/// are replayed as plain text instead of resuming a CLI session.
+pub(crate) fn assemble_prompt(
+    store: &HistoryStore,
+    conversation_id: &str,
+    prompt: &str,
+    budget: &TurnBudget,
+) -> AssembledPrompt {
+    let previous = store
+        .load(conversation_id)
+        .map(|conversation| conversation.turns)
+        .unwrap_or_default();
+
+    let mut used = prompt.chars().count();
+    let mut blocks = Vec::new();
+    for turn in previous
+        .iter()
+        .rev()
+        .filter(|turn| turn.status == TurnStatus::Success)
+        .take(budget.max_turns)
+    {
+        let block = format_turn(turn);
+        let length = block.chars().count();
+        if used + length > budget.max_chars {
+            break;
+        }
+        used += length;
+        blocks.push(block);
+    }
+
+    if blocks.is_empty() {
+        return AssembledPrompt {
+            prompt: prompt.to_string(),
+            prior_turns: 0,
+        };
+    }
+    blocks.reverse();
+    AssembledPrompt {
+        prior_turns: blocks.len(),
+        prompt: format!(
+            "Continue the conversation below. Earlier turns are included for context only.\n\n{}\nCurrent request:\n{prompt}",
+            blocks.concat()
+        ),
+    }
+}
+
+fn format_turn(turn: &Turn) -> String {
+    let attachments = if turn.context_files.is_empty() {
+        String::new()
+    } else {
+        format!(" (attached: {})", turn.context_files.join(", "))
+    };
+    format!(
+        "<previous_turn>\nUser{attachments}: {}\nAssistant: {}\n</previous_turn>\n",
+        turn.prompt.trim(),
+        turn.output.trim()
+    )
+}
+
+/// Copies `conversation_id` up to and including `turn_id` into a new
+/// conversation so it can continue in a different direction.
+#[tauri::command]
+pub(crate) fn fork_conversation(
+    app: tauri::AppHandle,
+    conversation_id: String,
+    turn_id: String,
+) -> Result<Conversation, String> {
+    let store = app.state::<HistoryStore>();
+    let source = store.load(&conversation_id)?;
+    let end = source
+        .turns
+        .iter()
+        .position(|turn| turn.id == turn_id)
+        .ok_or_else(|| format!("Turn {turn_id} not found in conversation {conversation_id}"))?;
+
+    let fork_id = history::new_id();
+    let fork = Conversation {
+        id: fork_id.clone(),
+        turns: source.turns[..=end]
+            .iter()
+            .map(|turn| Turn {
+                id: history::new_id(),
+                conversation_id: fork_id.clone(),
+                ..turn.clone()
+            })
+            .collect(),
+    };
+    store.save(&fork)?;
+    Ok(fork)
+}
@@ -0,0 +1,283 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use tauri::Manager;
+
+use crate::settings;
+
+/// Service name used for OS keychain entries.
+const KEYRING_SERVICE: &str = "com.ghc.app";
+/// Key under which the GitHub token is stored.
+pub(crate) const GITHUB_TOKEN_KEY: &str = "github-token";
+const CREDENTIALS_FILE: &str = "credentials.json";
+/// Written once the legacy `~/.env` token has been migrated.
+const MIGRATION_MARKER: &str = "credentials-migrated";
+
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum CredentialBackend {
+    /// Use the OS keychain when it is reachable, otherwise the file store.
+    /// Resolved once at startup and saved as the concrete backend; see
+    /// [`resolve_auto`].
+    Auto,
+    Keyring,
+    File,
+}
+
+/// Somewhere secrets can be kept.
+pub(crate) trait CredentialStore {
+    /// Human-readable name shown in status messages.
+    fn name(&self) -> &'static str;
+    fn load(&self, key: &str) -> Result<Option<String>, String>;
+    fn save(&self, key: &str, secret: &str) -> Result<(), String>;
+    fn delete(&self, key: &str) -> Result<(), String>;
+}
+
+/// Secret Service on Linux, Keychain on macOS, Credential Manager on Windows.
+struct KeyringStore;
+
+impl KeyringStore {
+    fn entry(key: &str) -> Result<keyring::Entry, String> {
+        keyring::Entry::new(KEYRING_SERVICE, key)
+            .map_err(|err| format!("Failed to open keychain entry: {err}"))
+    }
+
+    /// Whether the platform keychain can be reached at all.
+    fn available() -> bool {
+        match keyring::Entry::new(KEYRING_SERVICE, GITHUB_TOKEN_KEY) {
+            Ok(entry) => matches!(entry.get_password(), Ok(_) | Err(keyring::Error::NoEntry)),
+            Err(_) => false,
+        }
+    }
+}
+
+impl CredentialStore for KeyringStore {
+    fn name(&self) -> &'static str {
+        "the system keychain"
+    }
+
+    fn load(&self, key: &str) -> Result<Option<String>, String> {
+        match Self::entry(key)?.get_password() {
+            Ok(secret) => Ok(Some(secret)),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(err) => Err(format!("Failed to read from keychain: {err}")),
+        }
+    }
+
+    fn save(&self, key: &str, secret: &str) -> Result<(), String> {
+        Self::entry(key)?
+            .set_password(secret)
+            .map_err(|err| format!("Failed to write to keychain: {err}"))
+    }
+
+    fn delete(&self, key: &str) -> Result<(), String> {
+        match Self::entry(key)?.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(err) => Err(format!("Failed to delete from keychain: {err}")),
+        }
+    }
+}
+
+/// JSON map of secrets in the app config dir, readable only by the owner.
+struct FileStore {
+    path: PathBuf,
+}
+
+impl FileStore {
+    fn read(&self) -> BTreeMap<String, String> {
+        fs::read_to_string(&self.path)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    fn write(&self, secrets: &BTreeMap<String, String>) -> Result<(), String> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|err| format!("Failed to create config dir: {err}"))?;
+        }
+        let contents = serde_json::to_string_pretty(secrets)
+            .map_err(|err| format!("Failed to serialize credentials: {err}"))?;
+        write_private(&self.path, contents.as_bytes())
+            .map_err(|err| format!("Failed to write credentials file: {err}"))
+    }
+}
+
+impl CredentialStore for FileStore {
+    fn name(&self) -> &'static str {
+        "the ghc credentials file"
+    }
+
+    fn load(&self, key: &str) -> Result<Option<String>, String> {
+        Ok(self.read().remove(key))
+    }
+
+    fn save(&self, key: &str, secret: &str) -> Result<(), String> {
+        let mut secrets = self.read();
+        secrets.insert(key.to_string(), secret.to_string());
+        self.write(&secrets)
+    }
+
+    fn delete(&self, key: &str) -> Result<(), String> {
+        let mut secrets = self.read();
+        if secrets.remove(key).is_some() {
+            self.write(&secrets)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+fn write_private(path: &std::path::Path, contents: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+    use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)?;
+    // `mode` only applies to newly created files.
+    file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    file.write_all(contents)
+}
+
+#[cfg(not(unix))]
+fn write_private(path: &std::path::Path, contents: &[u8]) -> std::io::Result<()> {
+    // The app config dir already lives under the user's profile, which is
+    // private to them on Windows.
+    fs::write(path, contents)
+}
+
+fn config_dir(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    app.path()
+        .app_config_dir()
+        .map_err(|err| format!("Failed to resolve config dir: {err}"))
+}
+
+/// Picks the store configured in settings. An unresolved `auto` uses the
+/// file store for the session instead of probing the keychain on every call.
+pub(crate) fn active_store(app: &tauri::AppHandle) -> Result<Box<dyn CredentialStore>, String> {
+    store_for(app, settings::current(app).credential_backend)
+}
+
+fn store_for(
+    app: &tauri::AppHandle,
+    backend: CredentialBackend,
+) -> Result<Box<dyn CredentialStore>, String> {
+    let file_store = || -> Result<Box<dyn CredentialStore>, String> {
+        Ok(Box::new(FileStore {
+            path: config_dir(app)?.join(CREDENTIALS_FILE),
+        }))
+    };
+    match concrete(backend) {
+        CredentialBackend::Keyring => Ok(Box::new(KeyringStore)),
+        CredentialBackend::File | CredentialBackend::Auto => file_store(),
+    }
+}
+
+/// The backend actually used for `backend`: an unresolved `auto` is the file
+/// store.
+fn concrete(backend: CredentialBackend) -> CredentialBackend {
+    match backend {
+        CredentialBackend::Auto => CredentialBackend::File,
+        backend => backend,
+    }
+}
+
+/// The concrete backend `auto` stands for: the keychain when it can be
+/// reached, else the file store if it already holds secrets. `None` when
+/// neither is settled, e.g. the keychain is locked on a fresh install.
+pub(crate) fn resolve_auto(app: &tauri::AppHandle) -> Result<Option<CredentialBackend>, String> {
+    if KeyringStore::available() {
+        return Ok(Some(CredentialBackend::Keyring));
+    }
+    let file_store = FileStore {
+        path: config_dir(app)?.join(CREDENTIALS_FILE),
+    };
+    Ok((!file_store.read().is_empty()).then_some(CredentialBackend::File))
+}
+
+/// Copies the secrets under `keys` from the `from` backend to the `to`
+/// backend, then removes them from `from`. Nothing is removed unless every
+/// copy succeeded.
+pub(crate) fn move_secrets(
+    app: &tauri::AppHandle,
+    from: CredentialBackend,
+    to: CredentialBackend,
+    keys: &[String],
+) -> Result<(), String> {
+    if concrete(from) == concrete(to) {
+        return Ok(());
+    }
+    let source = store_for(app, from)?;
+    let target = store_for(app, to)?;
+    let mut moved = Vec::new();
+    for key in keys {
+        if let Some(secret) = source.load(key)? {
+            target.save(key, &secret)?;
+            moved.push(key);
+        }
+    }
+    for key in moved {
+        source.delete(key)?;
+    }
+    Ok(())
+}
+
+/// Moves a `GITHUB_TOKEN=` line that older versions wrote to `~/.env` (or
+/// the configured `legacy_env_file`) into the active credential store. Runs
+/// once per installation.
+pub(crate) fn migrate_env_token(app: &tauri::AppHandle) -> Result<(), String> {
+    let marker = config_dir(app)?.join(MIGRATION_MARKER);
+    if marker.exists() {
+        return Ok(());
+    }
+    let env_path = env_path(&settings::current(app))?;
+    let contents = fs::read_to_string(&env_path).unwrap_or_default();
+    let token = contents.lines().find_map(|line| {
+        line.trim()
+            .strip_prefix("GITHUB_TOKEN=")
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+    });
+
+    if let Some(token) = token {
+        let store = active_store(app)?;
+        if store.load(GITHUB_TOKEN_KEY)?.is_none() {
+            store.save(GITHUB_TOKEN_KEY, &token)?;
+        }
+        let updated: String = contents
+            .lines()
+            .filter(|line| !line.trim_start().starts_with("GITHUB_TOKEN="))
+            .map(|line| format!("{line}\n"))
+            .collect();
+        fs::write(&env_path, updated)
+            .map_err(|err| format!("Failed to write {}: {err}", env_path.display()))?;
+    }
+
+    if let Some(parent) = marker.parent() {
+        fs::create_dir_all(parent).map_err(|err| format!("Failed to create config dir: {err}"))?;
+    }
+    fs::write(&marker, "").map_err(|err| format!("Failed to record migration: {err}"))
+}
+
+/// The configured `legacy_env_file`, or `~/.env`.
+fn env_path(settings: &settings::Settings) -> Result<PathBuf, String> {
+    if let Some(path) = settings
+        .legacy_env_file
+        .as_deref()
+        .map(str::trim)
+        .filter(|path| !path.is_empty())
+    {
+        return Ok(PathBuf::from(path));
+    }
+    // Windows에서는 USERPROFILE을, 그 외에는 HOME을 사용하도록 수정합니다.
+    let home = env::var("HOME")
+        .or_else(|_| env::var("USERPROFILE"))
+        .map_err(|_| "Missing HOME or USERPROFILE environment variable".to_string())?;
+    Ok(PathBuf::from(home).join(".env"))
+}
@@ -0,0 +1,297 @@
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::env;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tauri::Manager;
+
+use crate::{accounts, context, credentials, discovery, github, history, settings};
+
+/// Most recent errors kept for diagnostics reports.
+const MAX_RECENT_ERRORS: usize = 50;
+/// Prefixes GitHub uses for its token formats.
+const TOKEN_PREFIXES: [&str; 6] = ["ghp_", "gho_", "ghu_", "ghs_", "ghr_", "github_pat_"];
+const REDACTED: &str = "[REDACTED]";
+
+#[derive(Clone, Serialize)]
+pub(crate) struct ErrorEntry {
+    /// Unix timestamp in milliseconds.
+    pub(crate) at: u64,
+    /// Area that failed, e.g. `copilot`, `login` or `install`.
+    pub(crate) source: &'static str,
+    pub(crate) message: String,
+}
+
+/// Ring buffer of errors seen since the app started.
+#[derive(Default)]
+pub(crate) struct ErrorLog(Mutex<VecDeque<ErrorEntry>>);
+
+/// Remembers `message` for the next diagnostics report.
+pub(crate) fn record_error(app: &tauri::AppHandle, source: &'static str, message: &str) {
+    let Some(log) = app.try_state::<ErrorLog>() else {
+        return;
+    };
+    let Ok(mut entries) = log.0.lock() else {
+        return;
+    };
+    if entries.len() == MAX_RECENT_ERRORS {
+        entries.pop_front();
+    }
+    entries.push_back(ErrorEntry {
+        at: history::now_millis(),
+        source,
+        message: message.to_string(),
+    });
+}
+
+/// Masks anything that looks like a GitHub token, the given `secrets`, and
+/// the user's home directory.
+fn redact(text: &str, secrets: &[String]) -> String {
+    let mut redacted = text.to_string();
+    for secret in secrets.iter().filter(|secret| !secret.is_empty()) {
+        redacted = redacted.replace(secret.as_str(), REDACTED);
+    }
+    for prefix in TOKEN_PREFIXES {
+        let mut output = String::with_capacity(redacted.len());
+        let mut rest = redacted.as_str();
+        while let Some(start) = rest.find(prefix) {
+            output.push_str(&rest[..start]);
+            let token = &rest[start..];
+            let end = token
+                .find(|ch: char| !(ch.is_ascii_alphanumeric() || ch == '_'))
+                .unwrap_or(token.len());
+            output.push_str(REDACTED);
+            rest = &token[end..];
+        }
+        output.push_str(rest);
+        redacted = output;
+    }
+    if let Some(home) = env::var("HOME")
+        .or_else(|_| env::var("USERPROFILE"))
+        .ok()
+        .filter(|home| home.len() > 1)
+    {
+        redacted = redacted.replace(&home, "~");
+    }
+    redacted
+}
+
+/// Summarises the shared temp dir and any staging dirs left behind.
+fn temp_dir_state(settings: &settings::Settings) -> String {
+    let root = context::temp_root(&settings.temp_dir_name);
+    let Ok(entries) = fs::read_dir(&root) else {
+        return format!("{} (missing)", root.display());
+    };
+    let mut staging = 0;
+    let mut other = 0;
+    for entry in entries.flatten() {
+        let is_staging = entry
+            .file_name()
+            .to_str()
+            .is_some_and(|name| name.starts_with(context::STAGING_PREFIX));
+        if is_staging {
+            staging += 1;
+        } else {
+            other += 1;
+        }
+    }
+    format!(
+        "{} ({staging} staging dirs, {other} other entries)",
+        root.display()
+    )
+}
+
+fn build_report(app: &tauri::AppHandle) -> String {
+    let settings = settings::current(app);
+    let mut report = String::new();
+    let info = app.package_info();
+    let _ = writeln!(report, "ghc diagnostics");
+    let _ = writeln!(
+        report,
+        "generated_at: {}",
+        history::utc_timestamp(history::now_millis())
+    );
+    let _ = writeln!(report, "app_version: {}", info.version);
+    let _ = writeln!(report, "os: {} ({})", env::consts::OS, env::consts::FAMILY);
+    let _ = writeln!(report, "arch: {}", env::consts::ARCH);
+
+    let _ = writeln!(report, "\n[copilot]");
+    let resolution = discovery::resolve(&settings);
+    match (&resolution.path, resolution.rule) {
+        (Some(path), Some(rule)) => {
+            let rule = serde_json::to_string(&rule).unwrap_or_default();
+            let _ = writeln!(report, "resolved: {} (rule {rule})", path.display());
+        }
+        _ => {
+            let _ = writeln!(report, "resolved: none");
+        }
+    }
+    let _ = writeln!(report, "candidates:");
+    for candidate in &resolution.candidates {
+        let mark = if candidate.found { "found" } else { "missing" };
+        let _ = writeln!(report, "  [{mark}] {}", candidate.path);
+    }
+    let mut command = crate::copilot_command_for(&settings, resolution.path.clone());
+    command.arg("--version");
+    let version = match crate::probe_output(command, settings.probe_timeout_secs) {
+        Ok(output) => {
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            format!(
+                "exit {:?}; stdout: {}; stderr: {}",
+                output.status.code(),
+                stdout.trim(),
+                stderr.trim()
+            )
+        }
+        Err(err) => serde_json::to_string(&err).unwrap_or_default(),
+    };
+    let _ = writeln!(report, "--version: {version}");
+
+    let _ = writeln!(report, "\n[path]");
+    for dir in env::split_paths(&discovery::augmented_path(&settings)) {
+        let _ = writeln!(report, "  {}", dir.display());
+    }
+
+    let _ = writeln!(report, "\n[github]");
+    let config = github::GitHubConfig::resolve(app);
+    let _ = writeln!(report, "host: {}", config.host);
+    let _ = writeln!(report, "api_base: {}", config.api_base);
+    let _ = writeln!(report, "client_id: {}", config.client_id);
+    let _ = writeln!(report, "scopes: {}", config.scopes.join(" "));
+    let env_token = env::var("GITHUB_TOKEN").is_ok_and(|token| !token.trim().is_empty());
+    let _ = writeln!(
+        report,
+        "env GITHUB_TOKEN: {}",
+        if env_token { "set" } else { "unset" }
+    );
+    let stored = matches!(accounts::token_for(app, None), Ok(Some(_)));
+    let _ = writeln!(
+        report,
+        "stored token: {}",
+        if stored { "present" } else { "absent" }
+    );
+    let _ = writeln!(
+        report,
+        "active account: {}",
+        accounts::active_login(app).unwrap_or_else(|| "none".to_string())
+    );
+
+    let _ = writeln!(report, "\n[temp]");
+    let _ = writeln!(report, "{}", temp_dir_state(&settings));
+
+    let _ = writeln!(report, "\n[settings]");
+    let _ = writeln!(
+        report,
+        "{}",
+        serde_json::to_string_pretty(&settings).unwrap_or_default()
+    );
+
+    let _ = writeln!(report, "\n[recent errors]");
+    let errors = app
+        .state::<ErrorLog>()
+        .0
+        .lock()
+        .map(|entries| entries.iter().cloned().collect::<Vec<_>>())
+        .unwrap_or_default();
+    if errors.is_empty() {
+        let _ = writeln!(report, "none");
+    }
+    for entry in errors {
+        let _ = writeln!(
+            report,
+            "{} {}: {}",
+            history::utc_timestamp(entry.at),
+            entry.source,
+            entry.message
+        );
+    }
+
+    // Redact the real tokens too, in case one was echoed in an error. Every
+    // stored account's token counts, not just the active one's.
+    let mut secrets: Vec<String> = env::var("GITHUB_TOKEN").into_iter().collect();
+    if let Ok(store) = credentials::active_store(app) {
+        secrets.extend(
+            accounts::secret_keys(app)
+                .iter()
+                .filter_map(|key| store.load(key).ok().flatten()),
+        );
+    }
+    let trimmed: Vec<String> = secrets
+        .iter()
+        .map(|secret| secret.trim().to_string())
+        .collect();
+    secrets.extend(trimmed);
+    redact(&report, &secrets)
+}
+
+/// Writes a redacted diagnostics report to `path`, or to the app log dir
+/// when omitted, and returns where it was written.
+#[tauri::command]
+pub(crate) async fn collect_diagnostics(
+    app: tauri::AppHandle,
+    path: Option<String>,
+) -> Result<String, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let path = match path {
+            Some(path) => PathBuf::from(path),
+            None => app
+                .path()
+                .app_log_dir()
+                .map_err(|err| format!("Failed to resolve log dir: {err}"))?
+                .join(format!("ghc-diagnostics-{}.txt", history::now_millis())),
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|err| format!("Failed to create report dir: {err}"))?;
+        }
+        fs::write(&path, build_report(&app))
+            .map_err(|err| format!("Failed to write diagnostics: {err}"))?;
+        Ok(path.display().to_string())
+    })
+    .await
+    .map_err(|err| format!("Failed to collect diagnostics: {err}"))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redacts_every_token_prefix() {
+        for prefix in TOKEN_PREFIXES {
+            let text = format!("token={prefix}AbC123_xyz; next");
+            assert_eq!(redact(&text, &[]), "token=[REDACTED]; next", "{prefix}");
+        }
+    }
+
+    #[test]
+    fn redacts_several_tokens_on_one_line() {
+        let text = "a ghp_one b github_pat_11AB_cd c";
+        assert_eq!(redact(text, &[]), "a [REDACTED] b [REDACTED] c");
+    }
+
+    #[test]
+    fn redacts_stored_secrets_without_a_known_prefix() {
+        let secrets = vec!["s3cr3t-value".to_string(), String::new()];
+        assert_eq!(
+            redact("Bearer s3cr3t-value failed", &secrets),
+            "Bearer [REDACTED] failed"
+        );
+    }
+
+    #[test]
+    fn masks_the_home_directory() {
+        let Some(home) = env::var("HOME")
+            .or_else(|_| env::var("USERPROFILE"))
+            .ok()
+            .filter(|home| home.len() > 1)
+        else {
+            return;
+        };
+        let text = format!("{home}/.config/ghc/settings.json");
+        assert_eq!(redact(&text, &[]), "~/.config/ghc/settings.json");
+    }
+}
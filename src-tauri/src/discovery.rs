@@ -0,0 +1,422 @@
+use serde::Serialize;
+use std::collections::HashSet;
+use std::env;
+#[cfg(target_os = "linux")]
+use std::fs;
+use std::path::{Path, PathBuf};
+#[cfg(not(target_os = "macos"))]
+use std::process::Stdio;
+
+#[cfg(target_os = "windows")]
+use std::os::windows::process::CommandExt;
+
+use crate::settings::Settings;
+
+/// Points ghc at a specific Copilot binary, after the `copilot_path` setting.
+const COPILOT_BIN_ENV: &str = "GHC_COPILOT_BIN";
+/// Names the Copilot CLI is installed under.
+const COPILOT_NAMES: [&str; 2] = ["copilot", "github-copilot"];
+
+/// The step of the resolution chain that produced a candidate.
+#[derive(Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum ResolutionRule {
+    /// The `copilot_path` setting.
+    Setting,
+    /// The `GHC_COPILOT_BIN` environment variable.
+    EnvVar,
+    /// A directory on [`augmented_path`].
+    PathSearch,
+    /// A well-known install location for the current platform.
+    PlatformCandidate,
+}
+
+#[derive(Clone, Serialize)]
+pub(crate) struct Candidate {
+    pub(crate) path: String,
+    pub(crate) rule: ResolutionRule,
+    /// Whether an executable file exists at `path`.
+    pub(crate) found: bool,
+}
+
+/// Outcome of looking for the Copilot binary, with every path examined on
+/// the way.
+#[derive(Default)]
+pub(crate) struct Resolution {
+    pub(crate) path: Option<PathBuf>,
+    pub(crate) rule: Option<ResolutionRule>,
+    pub(crate) candidates: Vec<Candidate>,
+    seen: HashSet<PathBuf>,
+}
+
+impl Resolution {
+    /// Records `path` as a candidate and returns whether it is usable.
+    fn examine(&mut self, rule: ResolutionRule, path: PathBuf) -> bool {
+        if !self.seen.insert(path.clone()) {
+            return false;
+        }
+        let found = is_executable(&path);
+        self.candidates.push(Candidate {
+            path: path.display().to_string(),
+            rule,
+            found,
+        });
+        if found {
+            self.path = Some(path);
+            self.rule = Some(rule);
+        }
+        found
+    }
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    path.metadata()
+        .map(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+/// File names to look for in each PATH directory.
+fn executable_names() -> Vec<String> {
+    if cfg!(target_os = "windows") {
+        let extensions = env::var("PATHEXT").unwrap_or_else(|_| ".COM;.EXE;.BAT;.CMD".to_string());
+        COPILOT_NAMES
+            .iter()
+            .flat_map(|name| {
+                extensions
+                    .split(';')
+                    .filter(|ext| !ext.is_empty())
+                    .map(move |ext| format!("{name}{}", ext.to_lowercase()))
+            })
+            .collect()
+    } else {
+        COPILOT_NAMES.iter().map(|name| name.to_string()).collect()
+    }
+}
+
+/// Finds the Copilot binary: the `copilot_path` setting, then
+/// `GHC_COPILOT_BIN`, then a PATH search over [`augmented_path`], then
+/// platform-specific locations.
+pub(crate) fn resolve(settings: &Settings) -> Resolution {
+    let mut resolution = Resolution::default();
+    if let Some(path) = settings
+        .copilot_path
+        .as_deref()
+        .map(str::trim)
+        .filter(|path| !path.is_empty())
+    {
+        if resolution.examine(ResolutionRule::Setting, PathBuf::from(path)) {
+            return resolution;
+        }
+    }
+    if let Some(path) = env::var_os(COPILOT_BIN_ENV).filter(|path| !path.is_empty()) {
+        if resolution.examine(ResolutionRule::EnvVar, PathBuf::from(path)) {
+            return resolution;
+        }
+    }
+    let names = executable_names();
+    for dir in env::split_paths(&augmented_path(settings)) {
+        if dir.as_os_str().is_empty() {
+            continue;
+        }
+        for name in &names {
+            if resolution.examine(ResolutionRule::PathSearch, dir.join(name)) {
+                return resolution;
+            }
+        }
+    }
+    for path in platform_candidates(settings) {
+        if resolution.examine(ResolutionRule::PlatformCandidate, path) {
+            return resolution;
+        }
+    }
+    resolution
+}
+
+/// Each Homebrew prefix, then MacPorts.
+#[cfg(target_os = "macos")]
+fn platform_candidates(settings: &Settings) -> Vec<PathBuf> {
+    homebrew_bin_dirs(settings)
+        .into_iter()
+        .chain([PathBuf::from("/opt/local/bin")])
+        .map(|dir| dir.join("copilot"))
+        .collect()
+}
+
+/// Whatever `where` finds, which also covers App Paths registrations.
+#[cfg(target_os = "windows")]
+fn platform_candidates(_settings: &Settings) -> Vec<PathBuf> {
+    let mut candidates = Vec::new();
+    for cmd in COPILOT_NAMES {
+        let mut command = std::process::Command::new("where");
+        command.arg(cmd);
+        command.creation_flags(crate::CREATE_NO_WINDOW);
+        command.stdin(Stdio::null());
+        command.stdout(Stdio::piped());
+        command.stderr(Stdio::piped());
+
+        if let Ok(output) = command.output() {
+            if output.status.success() {
+                let stdout = crate::decode_platform_bytes(&output.stdout);
+                candidates.extend(
+                    stdout
+                        .lines()
+                        .map(str::trim)
+                        .filter(|line| !line.is_empty())
+                        .map(PathBuf::from),
+                );
+            }
+        }
+    }
+    candidates
+}
+
+#[cfg(target_os = "linux")]
+fn platform_candidates(_settings: &Settings) -> Vec<PathBuf> {
+    linux_bin_dirs()
+        .into_iter()
+        .map(|dir| dir.join("copilot"))
+        .collect()
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
+fn platform_candidates(_settings: &Settings) -> Vec<PathBuf> {
+    Vec::new()
+}
+
+/// npm prefix that the Linux installer uses, so no root access is needed.
+#[cfg(target_os = "linux")]
+pub(crate) fn linux_user_prefix() -> Option<PathBuf> {
+    env::var_os("HOME").map(|home| PathBuf::from(home).join(".local"))
+}
+
+fn home_dir() -> Option<PathBuf> {
+    env::var_os("HOME")
+        .or_else(|| env::var_os("USERPROFILE"))
+        .map(PathBuf::from)
+}
+
+/// nvm's install dir, which holds one `versions/node/<version>` per Node.
+fn nvm_dir() -> Option<PathBuf> {
+    env::var_os("NVM_DIR")
+        .map(PathBuf::from)
+        .or_else(|| home_dir().map(|home| home.join(".nvm")))
+}
+
+fn volta_home() -> Option<PathBuf> {
+    env::var_os("VOLTA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| home_dir().map(|home| home.join(".volta")))
+}
+
+fn fnm_dirs() -> Vec<PathBuf> {
+    env::var_os("FNM_DIR")
+        .map(PathBuf::from)
+        .into_iter()
+        .chain(
+            home_dir()
+                .into_iter()
+                .flat_map(|home| [home.join(".local/share/fnm"), home.join(".fnm")]),
+        )
+        .collect()
+}
+
+/// Install roots of the Node version managers ghc knows about: nvm, volta
+/// and fnm. Anything under them was installed with npm.
+pub(crate) fn node_manager_roots() -> Vec<PathBuf> {
+    nvm_dir()
+        .into_iter()
+        .chain(volta_home())
+        .chain(fnm_dirs())
+        .collect()
+}
+
+/// Directories that commonly hold `copilot` or `npm` on Linux but are often
+/// missing from the PATH a desktop app is launched with.
+#[cfg(target_os = "linux")]
+fn linux_node_dirs() -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    if let Some(prefix) = linux_user_prefix() {
+        dirs.push(prefix.join("bin"));
+    }
+    dirs.push(PathBuf::from("/usr/local/bin"));
+
+    // nvm: the shell's active version, else the default alias. Other
+    // installed versions are left out so an old `node` never runs Copilot.
+    match env::var_os("NVM_BIN") {
+        Some(bin) => dirs.push(PathBuf::from(bin)),
+        None => dirs.extend(nvm_default_bin()),
+    }
+
+    // volta keeps shims in one place.
+    dirs.extend(volta_home().map(|volta| volta.join("bin")));
+
+    // fnm: the shell's multishell link, then the default alias.
+    if let Some(multishell) = env::var_os("FNM_MULTISHELL_PATH") {
+        dirs.push(PathBuf::from(multishell).join("bin"));
+    }
+    dirs.extend(
+        fnm_dirs()
+            .into_iter()
+            .map(|dir| dir.join("aliases/default/bin")),
+    );
+
+    dirs.retain(|dir| dir.is_dir());
+    dirs
+}
+
+/// Bin dir of the version nvm's `default` alias points to: the newest
+/// installed version matching it, compared as semver so `v20` beats `v9`.
+#[cfg(target_os = "linux")]
+fn nvm_default_bin() -> Option<PathBuf> {
+    nvm_alias_bin(&nvm_dir()?)
+}
+
+#[cfg(target_os = "linux")]
+fn nvm_alias_bin(dir: &Path) -> Option<PathBuf> {
+    // Aliases may name other aliases, e.g. `default` -> `lts/iron` -> `v20`.
+    let mut alias = "default".to_string();
+    for _ in 0..4 {
+        match fs::read_to_string(dir.join("alias").join(&alias)) {
+            Ok(target) => alias = target.trim().to_string(),
+            Err(_) => break,
+        }
+    }
+    if alias == "default" {
+        return None;
+    }
+    let wanted = alias.trim_start_matches('v');
+    let newest = matches!(wanted, "node" | "stable" | "lts/*");
+    fs::read_dir(dir.join("versions/node"))
+        .ok()?
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            let version = semver::Version::parse(name.trim_start_matches('v')).ok()?;
+            Some((version, entry.path()))
+        })
+        .filter(|(version, _)| {
+            let version = version.to_string();
+            newest || version == wanted || version.starts_with(&format!("{wanted}."))
+        })
+        .max_by(|a, b| a.0.cmp(&b.0))
+        .map(|(_, path)| path.join("bin"))
+}
+
+/// [`linux_node_dirs`] plus the global bin dir of whichever npm they hold.
+#[cfg(target_os = "linux")]
+fn linux_bin_dirs() -> Vec<PathBuf> {
+    // Asking npm takes a noticeable moment, so only do it once.
+    static NPM_BIN: std::sync::OnceLock<Option<PathBuf>> = std::sync::OnceLock::new();
+    let mut dirs = linux_node_dirs();
+    let npm_bin = NPM_BIN.get_or_init(|| {
+        // Same order as `augmented_path`: the user's PATH first.
+        let search_path = env::join_paths(
+            env::split_paths(&env::var_os("PATH").unwrap_or_default()).chain(dirs.iter().cloned()),
+        )
+        .ok()?;
+        std::process::Command::new("npm")
+            .args(["prefix", "-g"])
+            .env("PATH", search_path)
+            .stdin(Stdio::null())
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .map(|output| {
+                PathBuf::from(crate::decode_platform_bytes(&output.stdout).trim()).join("bin")
+            })
+    });
+    if let Some(bin) = npm_bin {
+        if bin.is_dir() && !dirs.contains(bin) {
+            dirs.push(bin.clone());
+        }
+    }
+    dirs
+}
+
+/// `bin` under each configured Homebrew prefix.
+#[cfg(target_os = "macos")]
+fn homebrew_bin_dirs(settings: &Settings) -> Vec<PathBuf> {
+    settings
+        .homebrew_prefixes
+        .iter()
+        .map(|prefix| prefix.trim())
+        .filter(|prefix| !prefix.is_empty())
+        .map(|prefix| PathBuf::from(prefix).join("bin"))
+        .collect()
+}
+
+/// Install directories a desktop launch tends to leave off PATH.
+#[cfg(target_os = "macos")]
+fn extra_path_dirs(settings: &Settings) -> Vec<PathBuf> {
+    homebrew_bin_dirs(settings)
+}
+
+#[cfg(target_os = "linux")]
+fn extra_path_dirs(_settings: &Settings) -> Vec<PathBuf> {
+    linux_bin_dirs()
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "linux")))]
+fn extra_path_dirs(_settings: &Settings) -> Vec<PathBuf> {
+    Vec::new()
+}
+
+/// The current PATH with [`extra_path_dirs`] appended, so the user's own
+/// PATH still decides which `node` and `copilot` run.
+pub(crate) fn augmented_path(settings: &Settings) -> String {
+    let current = env::var("PATH").unwrap_or_default();
+    let separator = if cfg!(target_os = "windows") {
+        ";"
+    } else {
+        ":"
+    };
+    let extra: Vec<String> = extra_path_dirs(settings)
+        .iter()
+        .map(|dir| dir.display().to_string())
+        .collect();
+    if extra.is_empty() {
+        return current;
+    }
+    if current.is_empty() {
+        return extra.join(separator);
+    }
+    format!("{}{}{}", current, separator, extra.join(separator))
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nvm_default_alias_picks_the_newest_matching_version_by_semver() {
+        let dir = env::temp_dir().join(format!("ghc-nvm-test-{}", uuid::Uuid::new_v4()));
+        for version in ["v9.11.2", "v18.19.0", "v18.2.0", "v20.11.0"] {
+            fs::create_dir_all(dir.join("versions/node").join(version).join("bin")).unwrap();
+        }
+        fs::create_dir_all(dir.join("alias/lts")).unwrap();
+        let bin = |version: &str| Some(dir.join("versions/node").join(version).join("bin"));
+
+        assert_eq!(nvm_alias_bin(&dir), None);
+        fs::write(dir.join("alias/default"), "node\n").unwrap();
+        assert_eq!(nvm_alias_bin(&dir), bin("v20.11.0"));
+        fs::write(dir.join("alias/default"), "18").unwrap();
+        assert_eq!(nvm_alias_bin(&dir), bin("v18.19.0"));
+        fs::write(dir.join("alias/default"), "v9").unwrap();
+        assert_eq!(nvm_alias_bin(&dir), bin("v9.11.2"));
+        fs::write(dir.join("alias/default"), "lts/iron").unwrap();
+        fs::write(dir.join("alias/lts/iron"), "v20.11.0").unwrap();
+        assert_eq!(nvm_alias_bin(&dir), bin("v20.11.0"));
+        fs::write(dir.join("alias/default"), "22").unwrap();
+        assert_eq!(nvm_alias_bin(&dir), None);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,613 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use tauri::Manager;
+
+use crate::history::{self, Conversation, HistoryStore, Turn, TurnStatus};
+
+/// Marks a JSON file as a ghc conversation export.
+const EXPORT_FORMAT: &str = "ghc-conversation";
+/// Layout version of JSON and Markdown exports.
+const EXPORT_VERSION: u32 = 1;
+
+/// Markers that delimit a conversation in a Markdown export. They render as
+/// nothing, so the file reads as plain Markdown while staying parseable:
+///
+/// ```text
+/// <!-- ghc:conversation {"format":"ghc-conversation","version":1,"id":"…"} -->
+/// <!-- ghc:turn {"id":"…","model":"…","started_at":…,"finished_at":…,"status":"success",…} -->
+/// <!-- ghc:prompt -->
+/// …prompt…
+/// <!-- ghc:response -->
+/// …output…
+/// <!-- ghc:end -->
+/// ```
+///
+/// The JSON inside each marker has `>` escaped so it cannot close the
+/// comment early. Everywhere else a backslash follows every `<!-- ghc`, so
+/// nothing in a prompt or response (say, one about this very format) can
+/// pass for a marker; the importer removes it again.
+const MD_CONVERSATION: &str = "<!-- ghc:conversation ";
+const MD_TURN: &str = "<!-- ghc:turn ";
+const MD_PROMPT: &str = "<!-- ghc:prompt -->";
+const MD_RESPONSE: &str = "<!-- ghc:response -->";
+const MD_END: &str = "<!-- ghc:end -->";
+const MD_CLOSE: &str = " -->";
+/// Every marker starts with this.
+const MD_PREFIX: &str = "<!-- ghc";
+const MD_ESCAPED_PREFIX: &str = "<!-- ghc\\";
+
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum ExportFormat {
+    Markdown,
+    /// Standalone HTML with embedded CSS.
+    Html,
+    /// HTML laid out for printing or saving as PDF.
+    PrintHtml,
+    Json,
+}
+
+/// The JSON export: the stored conversation plus enough to recognise it.
+#[derive(Serialize, Deserialize)]
+struct ConversationExport {
+    format: String,
+    version: u32,
+    /// Unix timestamp in milliseconds.
+    #[serde(default)]
+    exported_at: u64,
+    id: String,
+    #[serde(default)]
+    title: String,
+    turns: Vec<Turn>,
+}
+
+/// Header of a Markdown export.
+#[derive(Serialize, Deserialize)]
+struct MarkdownHeader {
+    format: String,
+    version: u32,
+    id: String,
+}
+
+/// Turn metadata carried in a Markdown export's turn marker.
+#[derive(Serialize, Deserialize)]
+struct MarkdownTurn {
+    id: String,
+    model: String,
+    #[serde(default)]
+    account: Option<String>,
+    #[serde(default)]
+    context_files: Vec<String>,
+    started_at: u64,
+    finished_at: u64,
+    status: TurnStatus,
+    #[serde(default)]
+    exit_code: Option<i32>,
+    #[serde(default)]
+    error: Option<String>,
+}
+
+fn status_label(status: TurnStatus) -> &'static str {
+    match status {
+        TurnStatus::Success => "success",
+        TurnStatus::Failed => "failed",
+        TurnStatus::Cancelled => "cancelled",
+        TurnStatus::Timeout => "timeout",
+        TurnStatus::Refused => "refused",
+        TurnStatus::Error => "error",
+    }
+}
+
+fn title_of(conversation: &Conversation) -> String {
+    conversation
+        .turns
+        .first()
+        .map(|turn| history::title_from_prompt(&turn.prompt))
+        .filter(|title| !title.is_empty())
+        .unwrap_or_else(|| "Conversation".to_string())
+}
+
+/// Makes `text` safe to place between markers; see [`MD_CONVERSATION`].
+fn escape_markers(text: &str) -> String {
+    text.replace(MD_PREFIX, MD_ESCAPED_PREFIX)
+}
+
+fn unescape_markers(text: &str) -> String {
+    text.replace(MD_ESCAPED_PREFIX, MD_PREFIX)
+}
+
+/// JSON safe to embed in an HTML comment.
+fn comment_json<T: Serialize>(value: &T) -> Result<String, String> {
+    serde_json::to_string(value)
+        .map(|json| json.replace('>', "\\u003e"))
+        .map_err(|err| format!("Failed to serialize export: {err}"))
+}
+
+fn to_json(conversation: &Conversation) -> Result<String, String> {
+    serde_json::to_string_pretty(&ConversationExport {
+        format: EXPORT_FORMAT.to_string(),
+        version: EXPORT_VERSION,
+        exported_at: history::now_millis(),
+        id: conversation.id.clone(),
+        title: title_of(conversation),
+        turns: conversation.turns.clone(),
+    })
+    .map_err(|err| format!("Failed to serialize export: {err}"))
+}
+
+fn to_markdown(conversation: &Conversation) -> Result<String, String> {
+    let mut out = format!(
+        "# {}\n\n{MD_CONVERSATION}{}{MD_CLOSE}\n",
+        escape_markers(&title_of(conversation)),
+        comment_json(&MarkdownHeader {
+            format: EXPORT_FORMAT.to_string(),
+            version: EXPORT_VERSION,
+            id: conversation.id.clone(),
+        })?
+    );
+    for (index, turn) in conversation.turns.iter().enumerate() {
+        let meta = MarkdownTurn {
+            id: turn.id.clone(),
+            model: turn.model.clone(),
+            account: turn.account.clone(),
+            context_files: turn.context_files.clone(),
+            started_at: turn.started_at,
+            finished_at: turn.finished_at,
+            status: turn.status,
+            exit_code: turn.exit_code,
+            error: turn.error.clone(),
+        };
+        out.push_str(&format!("\n## Turn {}\n\n", index + 1));
+        out.push_str(&format!("{MD_TURN}{}{MD_CLOSE}\n\n", comment_json(&meta)?));
+        out.push_str(&format!("- Model: `{}`\n", escape_markers(&turn.model)));
+        out.push_str(&format!(
+            "- Started: {}\n",
+            history::utc_timestamp(turn.started_at)
+        ));
+        out.push_str(&format!("- Status: {}\n", status_label(turn.status)));
+        if !turn.context_files.is_empty() {
+            out.push_str(&format!(
+                "- Attached: {}\n",
+                escape_markers(&turn.context_files.join(", "))
+            ));
+        }
+        if let Some(error) = &turn.error {
+            out.push_str(&format!("- Error: {}\n", escape_markers(error)));
+        }
+        out.push_str(&format!(
+            "\n### Prompt\n\n{MD_PROMPT}\n{}\n\n### Response\n\n{MD_RESPONSE}\n{}\n{MD_END}\n",
+            escape_markers(turn.prompt.trim_end()),
+            escape_markers(turn.output.trim_end())
+        ));
+    }
+    Ok(out)
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+const HTML_STYLE: &str = "
+body { font-family: -apple-system, BlinkMacSystemFont, 'Segoe UI', sans-serif; max-width: 860px; margin: 2rem auto; padding: 0 1rem; color: #1f2328; line-height: 1.5; }
+h1 { font-size: 1.6rem; }
+.turn { border: 1px solid #d0d7de; border-radius: 8px; padding: 1rem 1.25rem; margin: 1.25rem 0; }
+.meta { color: #59636e; font-size: 0.85rem; margin: 0 0 0.75rem; }
+.label { font-weight: 600; font-size: 0.8rem; text-transform: uppercase; letter-spacing: 0.04em; color: #59636e; margin: 0.75rem 0 0.25rem; }
+pre { white-space: pre-wrap; word-wrap: break-word; font-family: ui-monospace, SFMono-Regular, Menlo, monospace; font-size: 0.85rem; background: #f6f8fa; border-radius: 6px; padding: 0.75rem; margin: 0; }
+.prompt pre { background: #eef4ff; }
+.error { color: #cf222e; }
+";
+
+/// Extra rules for the print layout: A4 pages, no boxes or backgrounds, and
+/// turns kept whole where they fit on a page.
+const PRINT_STYLE: &str = "
+@page { size: A4; margin: 18mm 16mm; }
+body { max-width: none; margin: 0; padding: 0; font-size: 11pt; }
+.turn { border: none; border-top: 1px solid #999; border-radius: 0; padding: 0.5rem 0 0; break-inside: avoid-page; }
+pre { background: none !important; border: 1px solid #ccc; font-size: 9pt; }
+";
+
+fn to_html(conversation: &Conversation, print: bool) -> String {
+    let title = escape_html(&title_of(conversation));
+    let mut body = String::new();
+    for (index, turn) in conversation.turns.iter().enumerate() {
+        let mut meta = format!(
+            "Turn {} · {} · {} · {}",
+            index + 1,
+            escape_html(&turn.model),
+            history::utc_timestamp(turn.started_at),
+            status_label(turn.status)
+        );
+        if !turn.context_files.is_empty() {
+            meta.push_str(&format!(
+                " · attached: {}",
+                escape_html(&turn.context_files.join(", "))
+            ));
+        }
+        body.push_str(&format!(
+            "<section class=\"turn\">\n<p class=\"meta\">{meta}</p>\n<div class=\"prompt\"><p class=\"label\">Prompt</p><pre>{}</pre></div>\n<div class=\"response\"><p class=\"label\">Response</p><pre>{}</pre></div>\n",
+            escape_html(turn.prompt.trim_end()),
+            escape_html(turn.output.trim_end())
+        ));
+        if let Some(error) = &turn.error {
+            body.push_str(&format!("<p class=\"error\">{}</p>\n", escape_html(error)));
+        }
+        body.push_str("</section>\n");
+    }
+    let print_style = if print { PRINT_STYLE } else { "" };
+    format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<meta name=\"generator\" content=\"ghc\">\n<title>{title}</title>\n<style>{HTML_STYLE}{print_style}</style>\n</head>\n<body>\n<h1>{title}</h1>\n<p class=\"meta\">Exported {}</p>\n{body}</body>\n</html>\n",
+        history::utc_timestamp(history::now_millis())
+    )
+}
+
+/// Writes conversation `id` to `path` in `format` and returns the path.
+#[tauri::command]
+pub(crate) async fn export_conversation(
+    app: tauri::AppHandle,
+    id: String,
+    format: ExportFormat,
+    path: String,
+) -> Result<String, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let conversation = app.state::<HistoryStore>().load(&id)?;
+        if conversation.turns.is_empty() {
+            return Err(format!("Conversation {id} has no turns to export"));
+        }
+        let contents = match format {
+            ExportFormat::Markdown => to_markdown(&conversation)?,
+            ExportFormat::Html => to_html(&conversation, false),
+            ExportFormat::PrintHtml => to_html(&conversation, true),
+            ExportFormat::Json => to_json(&conversation)?,
+        };
+        let path = PathBuf::from(path);
+        if let Some(parent) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            fs::create_dir_all(parent)
+                .map_err(|err| format!("Failed to create export dir: {err}"))?;
+        }
+        fs::write(&path, contents).map_err(|err| format!("Failed to write export: {err}"))?;
+        Ok(path.display().to_string())
+    })
+    .await
+    .map_err(|err| format!("Failed to export conversation: {err}"))?
+}
+
+/// Largest file `import_conversation` reads.
+const MAX_IMPORT_BYTES: u64 = 32 * 1024 * 1024;
+
+#[derive(Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum ImportStatus {
+    /// The conversation was new.
+    Created,
+    /// The conversation existed; turns it lacked were added.
+    Merged,
+    /// Every turn was already in history.
+    Unchanged,
+}
+
+#[derive(Serialize)]
+pub(crate) struct ImportResult {
+    status: ImportStatus,
+    turns_added: usize,
+    /// The conversation as stored after the import.
+    conversation: Conversation,
+}
+
+/// Splits `text` around the first `marker`, dropping the marker.
+fn split_at_marker<'a>(text: &'a str, marker: &str) -> Option<(&'a str, &'a str)> {
+    let index = text.find(marker)?;
+    Some((&text[..index], &text[index + marker.len()..]))
+}
+
+/// Reads the JSON of a `<!-- ghc:… {json} -->` marker at the start of `text`.
+fn marker_json<T: serde::de::DeserializeOwned>(
+    text: &str,
+    marker: &str,
+) -> Result<(T, String), String> {
+    let (_, rest) = split_at_marker(text, marker)
+        .ok_or_else(|| format!("Missing `{}` marker", marker.trim()))?;
+    let (json, rest) = split_at_marker(rest, MD_CLOSE)
+        .ok_or_else(|| format!("Unterminated `{}` marker", marker.trim()))?;
+    let value = serde_json::from_str(json)
+        .map_err(|err| format!("Invalid `{}` marker: {err}", marker.trim()))?;
+    Ok((value, rest.to_string()))
+}
+
+/// Drops the line break that follows a marker.
+fn after_marker_line(text: &str) -> &str {
+    text.strip_prefix("\r\n")
+        .or_else(|| text.strip_prefix('\n'))
+        .unwrap_or(text)
+}
+
+/// Parses the Markdown layout described at [`MD_CONVERSATION`].
+fn parse_markdown(text: &str) -> Result<ConversationExport, String> {
+    let (header, mut rest) = marker_json::<MarkdownHeader>(text, MD_CONVERSATION)?;
+    let mut turns = Vec::new();
+    while rest.contains(MD_TURN) {
+        let (meta, after_meta) = marker_json::<MarkdownTurn>(&rest, MD_TURN)?;
+        let (_, after_prompt) = split_at_marker(&after_meta, MD_PROMPT)
+            .ok_or_else(|| format!("Turn {} has no prompt marker", meta.id))?;
+        let (prompt, after_response) = split_at_marker(after_prompt, MD_RESPONSE)
+            .ok_or_else(|| format!("Turn {} has no response marker", meta.id))?;
+        let (output, after_end) = split_at_marker(after_response, MD_END)
+            .ok_or_else(|| format!("Turn {} has no end marker", meta.id))?;
+        // The `### Response` heading sits between the prompt and its marker.
+        let prompt = prompt.trim_end();
+        let prompt = prompt.strip_suffix("### Response").unwrap_or(prompt);
+        turns.push(Turn {
+            id: meta.id,
+            conversation_id: header.id.clone(),
+            prompt: unescape_markers(after_marker_line(prompt).trim_end()),
+            model: meta.model,
+            account: meta.account,
+            context_files: meta.context_files,
+            output: unescape_markers(after_marker_line(output).trim_end()),
+            started_at: meta.started_at,
+            finished_at: meta.finished_at,
+            status: meta.status,
+            exit_code: meta.exit_code,
+            error: meta.error,
+        });
+        rest = after_end.to_string();
+    }
+    Ok(ConversationExport {
+        format: header.format,
+        version: header.version,
+        exported_at: 0,
+        id: header.id,
+        title: String::new(),
+        turns,
+    })
+}
+
+/// Checks an export before it touches history and ties every turn to the
+/// conversation.
+fn validate_import(export: ConversationExport) -> Result<Conversation, String> {
+    if export.format != EXPORT_FORMAT {
+        return Err(format!("Not a ghc conversation export ({})", export.format));
+    }
+    if export.version > EXPORT_VERSION {
+        return Err(format!(
+            "Export version {} is newer than this ghc supports ({EXPORT_VERSION})",
+            export.version
+        ));
+    }
+    if export.turns.is_empty() {
+        return Err("The export has no turns".to_string());
+    }
+    let mut seen = std::collections::HashSet::new();
+    for turn in &export.turns {
+        if turn.id.trim().is_empty() || turn.model.trim().is_empty() {
+            return Err("Every turn needs an id and a model".to_string());
+        }
+        if !seen.insert(turn.id.as_str()) {
+            return Err(format!("Turn {} appears twice", turn.id));
+        }
+        if turn.finished_at != 0 && turn.finished_at < turn.started_at {
+            return Err(format!("Turn {} finishes before it starts", turn.id));
+        }
+    }
+    let id = export.id;
+    Ok(Conversation {
+        turns: export
+            .turns
+            .into_iter()
+            .map(|turn| Turn {
+                conversation_id: id.clone(),
+                ..turn
+            })
+            .collect(),
+        id,
+    })
+}
+
+/// Adds a JSON or Markdown export to history. A conversation whose id is
+/// already stored gains only the turns it lacks, so importing the same file
+/// twice changes nothing.
+#[tauri::command]
+pub(crate) async fn import_conversation(
+    app: tauri::AppHandle,
+    path: String,
+) -> Result<ImportResult, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let size = fs::metadata(&path)
+            .map_err(|err| format!("Failed to read {path}: {err}"))?
+            .len();
+        if size > MAX_IMPORT_BYTES {
+            return Err(format!(
+                "{path} is too large to import ({size} bytes, limit {MAX_IMPORT_BYTES})"
+            ));
+        }
+        let text =
+            fs::read_to_string(&path).map_err(|err| format!("Failed to read {path}: {err}"))?;
+        let export = if text.trim_start().starts_with('{') {
+            serde_json::from_str::<ConversationExport>(&text)
+                .map_err(|err| format!("Invalid conversation JSON: {err}"))?
+        } else {
+            parse_markdown(&text)?
+        };
+        let imported = validate_import(export)?;
+
+        let store = app.state::<HistoryStore>();
+        if !store.contains(&imported.id)? {
+            store.save(&imported)?;
+            return Ok(ImportResult {
+                status: ImportStatus::Created,
+                turns_added: imported.turns.len(),
+                conversation: imported,
+            });
+        }
+        let mut existing = store.load(&imported.id)?;
+        let before = existing.turns.len();
+        for turn in imported.turns {
+            if !existing.turns.iter().any(|known| known.id == turn.id) {
+                existing.turns.push(turn);
+            }
+        }
+        let turns_added = existing.turns.len() - before;
+        if turns_added == 0 {
+            return Ok(ImportResult {
+                status: ImportStatus::Unchanged,
+                turns_added,
+                conversation: existing,
+            });
+        }
+        existing.turns.sort_by_key(|turn| turn.started_at);
+        store.save(&existing)?;
+        Ok(ImportResult {
+            status: ImportStatus::Merged,
+            turns_added,
+            conversation: existing,
+        })
+    })
+    .await
+    .map_err(|err| format!("Failed to import conversation: {err}"))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn turn(id: &str, prompt: &str, output: &str) -> Turn {
+        Turn {
+            id: id.to_string(),
+            conversation_id: "conversation".to_string(),
+            prompt: prompt.to_string(),
+            model: "gpt-5-mini".to_string(),
+            account: Some("octocat".to_string()),
+            context_files: vec!["notes.md".to_string()],
+            output: output.to_string(),
+            started_at: 1_700_000_000_000,
+            finished_at: 1_700_000_005_000,
+            status: TurnStatus::Success,
+            exit_code: Some(0),
+            error: None,
+        }
+    }
+
+    fn conversation(turns: Vec<Turn>) -> Conversation {
+        Conversation {
+            id: "conversation".to_string(),
+            turns,
+        }
+    }
+
+    fn markdown_round_trip(conversation: &Conversation) -> Conversation {
+        let markdown = to_markdown(conversation).unwrap();
+        validate_import(parse_markdown(&markdown).unwrap()).unwrap()
+    }
+
+    fn assert_same_turns(before: &Conversation, after: &Conversation) {
+        assert_eq!(after.id, before.id);
+        assert_eq!(after.turns.len(), before.turns.len());
+        for (before, after) in before.turns.iter().zip(&after.turns) {
+            assert_eq!(after.id, before.id);
+            assert_eq!(after.conversation_id, before.conversation_id);
+            assert_eq!(after.prompt, before.prompt);
+            assert_eq!(after.output, before.output);
+            assert_eq!(after.model, before.model);
+            assert_eq!(after.account, before.account);
+            assert_eq!(after.context_files, before.context_files);
+            assert_eq!(after.started_at, before.started_at);
+            assert_eq!(after.finished_at, before.finished_at);
+            assert!(after.status == before.status);
+            assert_eq!(after.exit_code, before.exit_code);
+            assert_eq!(after.error, before.error);
+        }
+    }
+
+    #[test]
+    fn markdown_round_trip_keeps_every_turn() {
+        let original = conversation(vec![
+            turn("a", "First question", "An answer\n\nwith **markdown**"),
+            turn("b", "Second", "```rust\nfn main() {}\n```"),
+        ]);
+        assert_same_turns(&original, &markdown_round_trip(&original));
+    }
+
+    #[test]
+    fn markdown_round_trip_survives_markers_in_text() {
+        let prompt = format!(
+            "Why does {MD_RESPONSE} split here?\n{MD_TURN}{{\"id\":\"x\"}}{MD_CLOSE}\n### Response"
+        );
+        let output = format!("Because of {MD_END}.\nA literal <!-- ghc\\ stays.\n{MD_PROMPT}");
+        let mut tricky = turn("a", &prompt, &output);
+        tricky.status = TurnStatus::Failed;
+        tricky.error = Some(format!("stderr: {MD_END}"));
+        let original = conversation(vec![tricky, turn("b", "next", "done")]);
+        assert_same_turns(&original, &markdown_round_trip(&original));
+    }
+
+    #[test]
+    fn markdown_has_one_set_of_markers_per_turn() {
+        let markdown = to_markdown(&conversation(vec![turn("a", MD_END, MD_RESPONSE)])).unwrap();
+        assert_eq!(markdown.matches(MD_CONVERSATION).count(), 1);
+        assert_eq!(markdown.matches(MD_TURN).count(), 1);
+        assert_eq!(markdown.matches(MD_RESPONSE).count(), 1);
+        assert_eq!(markdown.matches(MD_END).count(), 1);
+    }
+
+    #[test]
+    fn marker_escaping_is_reversible() {
+        for text in [
+            "<!-- ghc",
+            "<!-- ghc\\",
+            "<!-- ghc\\\\:end -->",
+            "a <!-- ghc:end --> b <!-- ghc:end -->",
+            "<!-- other -->",
+        ] {
+            let escaped = escape_markers(text);
+            assert!(!escaped.contains("<!-- ghc:"));
+            assert_eq!(unescape_markers(&escaped), text);
+        }
+    }
+
+    #[test]
+    fn json_round_trip_keeps_every_turn() {
+        let original = conversation(vec![turn("a", "q", "a"), turn("b", "q2", "")]);
+        let export: ConversationExport =
+            serde_json::from_str(&to_json(&original).unwrap()).unwrap();
+        assert_eq!(export.format, EXPORT_FORMAT);
+        assert_same_turns(&original, &validate_import(export).unwrap());
+    }
+
+    #[test]
+    fn import_rejects_bad_exports() {
+        assert!(parse_markdown("# Just some notes\n").is_err());
+
+        let duplicate = conversation(vec![turn("a", "q", "a"), turn("a", "q2", "a2")]);
+        let parsed = parse_markdown(&to_markdown(&duplicate).unwrap()).unwrap();
+        assert!(validate_import(parsed).is_err());
+
+        let json = to_json(&conversation(vec![turn("a", "q", "a")])).unwrap();
+        let mut foreign: ConversationExport = serde_json::from_str(&json).unwrap();
+        foreign.format = "something-else".to_string();
+        assert!(validate_import(foreign).is_err());
+
+        let mut newer: ConversationExport = serde_json::from_str(&json).unwrap();
+        newer.version = EXPORT_VERSION + 1;
+        assert!(validate_import(newer).is_err());
+
+        let mut backwards = turn("a", "q", "a");
+        backwards.finished_at = backwards.started_at - 1;
+        let parsed = parse_markdown(&to_markdown(&conversation(vec![backwards])).unwrap()).unwrap();
+        assert!(validate_import(parsed).is_err());
+    }
+}
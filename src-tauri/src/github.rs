@@ -0,0 +1,243 @@
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fmt;
+
+use crate::settings;
+
+/// Environment variables that override the matching settings.
+const HOST_ENV: &str = "GHC_GITHUB_HOST";
+/// Overrides the API base, e.g. to point at a mock server.
+const API_BASE_ENV: &str = "GHC_GITHUB_API_BASE";
+const CLIENT_ID_ENV: &str = "GHC_OAUTH_CLIENT_ID";
+/// Comma- or space-separated list.
+const SCOPES_ENV: &str = "GHC_OAUTH_SCOPES";
+const PUBLIC_HOST: &str = "github.com";
+
+/// Where and how to talk to GitHub, from settings plus environment
+/// overrides.
+#[derive(Clone)]
+pub(crate) struct GitHubConfig {
+    /// Bare host name such as `github.com` or `ghe.example.com`.
+    pub(crate) host: String,
+    /// Base URL of the REST API, without a trailing slash.
+    pub(crate) api_base: String,
+    pub(crate) client_id: String,
+    pub(crate) scopes: Vec<String>,
+}
+
+fn env_override(name: &str) -> Option<String> {
+    env::var(name)
+        .ok()
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+impl GitHubConfig {
+    pub(crate) fn resolve(app: &tauri::AppHandle) -> Self {
+        let settings = settings::current(app);
+        let host = env_override(HOST_ENV).unwrap_or(settings.github_host);
+        let host = host
+            .trim()
+            .trim_start_matches("https://")
+            .trim_start_matches("http://")
+            .trim_end_matches('/')
+            .to_string();
+        let api_base = env_override(API_BASE_ENV)
+            .or(settings
+                .github_api_base
+                .filter(|base| !base.trim().is_empty()))
+            .unwrap_or_else(|| {
+                // GitHub Enterprise Server serves the API under /api/v3.
+                if host == PUBLIC_HOST {
+                    "https://api.github.com".to_string()
+                } else {
+                    format!("https://{host}/api/v3")
+                }
+            });
+        let scopes = match env_override(SCOPES_ENV) {
+            Some(scopes) => scopes
+                .split(|ch: char| ch == ',' || ch.is_whitespace())
+                .filter(|scope| !scope.is_empty())
+                .map(str::to_string)
+                .collect(),
+            None => settings.oauth_scopes,
+        };
+        GitHubConfig {
+            host,
+            api_base: api_base.trim().trim_end_matches('/').to_string(),
+            client_id: env_override(CLIENT_ID_ENV).unwrap_or(settings.oauth_client_id),
+            scopes,
+        }
+    }
+
+    pub(crate) fn is_enterprise(&self) -> bool {
+        self.host != PUBLIC_HOST
+    }
+
+    pub(crate) fn device_code_url(&self) -> String {
+        format!("https://{}/login/device/code", self.host)
+    }
+
+    pub(crate) fn access_token_url(&self) -> String {
+        format!("https://{}/login/oauth/access_token", self.host)
+    }
+}
+
+/// What GitHub reports about a token.
+#[derive(Serialize)]
+pub(crate) struct TokenInfo {
+    pub(crate) login: String,
+    pub(crate) name: Option<String>,
+    pub(crate) avatar_url: Option<String>,
+    /// Scopes granted to an OAuth token. `None` for token types GitHub does
+    /// not report scopes for, such as fine-grained personal access tokens.
+    pub(crate) scopes: Option<Vec<String>>,
+    /// Expiry as reported by GitHub, e.g. `2025-01-31 12:00:00 UTC`. `None`
+    /// when the token does not expire.
+    pub(crate) expires_at: Option<String>,
+    /// Whether the account has a Copilot seat. `None` when GitHub could not
+    /// be asked.
+    pub(crate) copilot_access: Option<bool>,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub(crate) enum TokenError {
+    NoToken,
+    /// GitHub no longer accepts the token.
+    Revoked,
+    MissingScopes {
+        missing: Vec<String>,
+    },
+    /// GitHub could not be reached.
+    Network {
+        message: String,
+    },
+    Failed {
+        message: String,
+    },
+}
+
+impl fmt::Display for TokenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenError::NoToken => write!(f, "No GitHub token found. Log in with GitHub first."),
+            TokenError::Revoked => write!(
+                f,
+                "GitHub rejected the token; it was revoked or has expired. Log in again."
+            ),
+            TokenError::MissingScopes { missing } => write!(
+                f,
+                "The GitHub token is missing the {} scope. Log in again to grant it.",
+                missing.join(", ")
+            ),
+            TokenError::Network { message } | TokenError::Failed { message } => {
+                write!(f, "{message}")
+            }
+        }
+    }
+}
+
+impl From<String> for TokenError {
+    fn from(message: String) -> Self {
+        TokenError::Failed { message }
+    }
+}
+
+fn get(base: &str, path: &str, token: &str) -> Result<reqwest::blocking::Response, TokenError> {
+    reqwest::blocking::Client::new()
+        .get(format!("{base}{path}"))
+        .header("Accept", "application/vnd.github+json")
+        .header("User-Agent", "ghc")
+        .bearer_auth(token)
+        .send()
+        .map_err(|err| TokenError::Network {
+            message: format!("Failed to reach GitHub at {base}: {err}"),
+        })
+}
+
+fn header(response: &reqwest::blocking::Response, name: &str) -> Option<String> {
+    response
+        .headers()
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+/// Whether `granted` covers `scope`; `user` implies `read:user` and so on.
+fn scope_granted(granted: &[String], scope: &str) -> bool {
+    granted.iter().any(|entry| entry == scope)
+        || scope
+            .strip_prefix("read:")
+            .is_some_and(|parent| granted.iter().any(|entry| entry == parent))
+}
+
+/// Checks `token` against the GitHub user API and reports who it belongs to.
+pub(crate) fn verify_token(app: &tauri::AppHandle, token: &str) -> Result<TokenInfo, TokenError> {
+    #[derive(Deserialize)]
+    struct User {
+        login: String,
+        name: Option<String>,
+        avatar_url: Option<String>,
+    }
+
+    let config = GitHubConfig::resolve(app);
+    let base = config.api_base.as_str();
+    let response = get(base, "/user", token)?;
+    let status = response.status();
+    if status == reqwest::StatusCode::UNAUTHORIZED {
+        return Err(TokenError::Revoked);
+    }
+    if !status.is_success() {
+        return Err(TokenError::Failed {
+            message: format!("GitHub user lookup failed ({status})"),
+        });
+    }
+    let scopes = header(&response, "x-oauth-scopes").map(|value| {
+        value
+            .split(',')
+            .map(|scope| scope.trim().to_string())
+            .filter(|scope| !scope.is_empty())
+            .collect::<Vec<_>>()
+    });
+    let expires_at = header(&response, "github-authentication-token-expiration");
+    let user = response.json::<User>().map_err(|err| TokenError::Failed {
+        message: format!("Failed to parse GitHub user: {err}"),
+    })?;
+
+    if let Some(granted) = scopes.as_deref() {
+        let missing: Vec<String> = config
+            .scopes
+            .iter()
+            .filter(|scope| !scope_granted(granted, scope))
+            .map(|scope| scope.to_string())
+            .collect();
+        if !missing.is_empty() {
+            return Err(TokenError::MissingScopes { missing });
+        }
+    }
+
+    // The same endpoint the Copilot CLI uses to look up the user's seat.
+    let copilot_access = match get(base, "/copilot_internal/user", token) {
+        Ok(response) if response.status().is_success() => Some(true),
+        Ok(response)
+            if matches!(
+                response.status(),
+                reqwest::StatusCode::FORBIDDEN | reqwest::StatusCode::NOT_FOUND
+            ) =>
+        {
+            Some(false)
+        }
+        _ => None,
+    };
+
+    Ok(TokenInfo {
+        login: user.login,
+        name: user.name,
+        avatar_url: user.avatar_url,
+        scopes,
+        expires_at,
+        copilot_access,
+    })
+}
@@ -0,0 +1,334 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::Manager;
+
+/// Longest title derived from a conversation's first prompt.
+const TITLE_CHARS: usize = 80;
+
+#[derive(Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum TurnStatus {
+    Success,
+    /// Copilot exited with a non-zero status.
+    Failed,
+    Cancelled,
+    Timeout,
+    /// The context policy or the usage budget stopped the run before Copilot
+    /// ran.
+    Refused,
+    /// Copilot could not be started or the app failed around it.
+    Error,
+}
+
+/// One `run_copilot` call.
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) struct Turn {
+    pub(crate) id: String,
+    pub(crate) conversation_id: String,
+    pub(crate) prompt: String,
+    pub(crate) model: String,
+    /// GitHub account the run was made with, when one was chosen.
+    #[serde(default)]
+    pub(crate) account: Option<String>,
+    /// File names of the attached context, without their directories.
+    #[serde(default)]
+    pub(crate) context_files: Vec<String>,
+    #[serde(default)]
+    pub(crate) output: String,
+    /// Unix timestamps in milliseconds.
+    pub(crate) started_at: u64,
+    pub(crate) finished_at: u64,
+    pub(crate) status: TurnStatus,
+    #[serde(default)]
+    pub(crate) exit_code: Option<i32>,
+    #[serde(default)]
+    pub(crate) error: Option<String>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) struct Conversation {
+    pub(crate) id: String,
+    pub(crate) turns: Vec<Turn>,
+}
+
+#[derive(Serialize)]
+pub(crate) struct ConversationSummary {
+    pub(crate) id: String,
+    pub(crate) title: String,
+    pub(crate) model: String,
+    pub(crate) turn_count: usize,
+    pub(crate) created_at: u64,
+    pub(crate) updated_at: u64,
+    pub(crate) last_status: TurnStatus,
+}
+
+impl Conversation {
+    fn summary(&self) -> Option<ConversationSummary> {
+        let first = self.turns.first()?;
+        let last = self.turns.last()?;
+        Some(ConversationSummary {
+            id: self.id.clone(),
+            title: title_from_prompt(&first.prompt),
+            model: last.model.clone(),
+            turn_count: self.turns.len(),
+            created_at: first.started_at,
+            updated_at: last.finished_at,
+            last_status: last.status,
+        })
+    }
+}
+
+pub(crate) fn title_from_prompt(prompt: &str) -> String {
+    let line = prompt.lines().next().unwrap_or_default().trim();
+    if line.chars().count() <= TITLE_CHARS {
+        return line.to_string();
+    }
+    let mut title: String = line.chars().take(TITLE_CHARS - 1).collect();
+    title.push('…');
+    title
+}
+
+pub(crate) fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+const MILLIS_PER_DAY: u64 = 24 * 60 * 60 * 1000;
+
+/// Converts days since the Unix epoch to a UTC `(year, month, day)`.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    // Howard Hinnant's days_from_civil, inverted.
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// UTC date of `millis` as `YYYY-MM-DD`.
+pub(crate) fn utc_date(millis: u64) -> String {
+    let (year, month, day) = civil_from_days((millis / MILLIS_PER_DAY) as i64);
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+/// UTC time of `millis` as `YYYY-MM-DD HH:MM:SS UTC`.
+pub(crate) fn utc_timestamp(millis: u64) -> String {
+    let secs = (millis % MILLIS_PER_DAY) / 1000;
+    format!(
+        "{} {:02}:{:02}:{:02} UTC",
+        utc_date(millis),
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+pub(crate) fn new_id() -> String {
+    uuid::Uuid::new_v4().to_string()
+}
+
+/// Conversations stored as one JSON-lines file per conversation, one turn per
+/// line, under `<app data dir>/history`.
+pub(crate) struct HistoryStore {
+    dir: PathBuf,
+    lock: Mutex<()>,
+    /// Bumped on every write so derived data such as the search index can
+    /// tell when it is stale.
+    revision: AtomicU64,
+}
+
+impl HistoryStore {
+    pub(crate) fn new(app: &tauri::AppHandle) -> Result<Self, String> {
+        let dir = app
+            .path()
+            .app_data_dir()
+            .map_err(|err| format!("Failed to resolve data dir: {err}"))?
+            .join("history");
+        Ok(HistoryStore {
+            dir,
+            lock: Mutex::new(()),
+            revision: AtomicU64::new(0),
+        })
+    }
+
+    pub(crate) fn revision(&self) -> u64 {
+        self.revision.load(Ordering::SeqCst)
+    }
+
+    fn bump_revision(&self) {
+        self.revision.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn conversation_path(&self, id: &str) -> Result<PathBuf, String> {
+        let valid = !id.is_empty()
+            && id
+                .chars()
+                .all(|ch| ch.is_ascii_alphanumeric() || ch == '-' || ch == '_');
+        if !valid {
+            return Err(format!("Invalid conversation id: {id}"));
+        }
+        Ok(self.dir.join(format!("{id}.jsonl")))
+    }
+
+    pub(crate) fn append_turn(&self, turn: &Turn) -> Result<(), String> {
+        let path = self.conversation_path(&turn.conversation_id)?;
+        let line = serde_json::to_string(turn)
+            .map_err(|err| format!("Failed to serialize turn: {err}"))?;
+        let _guard = self.lock.lock().map_err(|_| "History store is poisoned")?;
+        fs::create_dir_all(&self.dir)
+            .map_err(|err| format!("Failed to create history dir: {err}"))?;
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|err| format!("Failed to open history file: {err}"))?;
+        writeln!(file, "{line}").map_err(|err| format!("Failed to write history: {err}"))?;
+        self.bump_revision();
+        Ok(())
+    }
+
+    /// Replaces the stored turns of `conversation` wholesale.
+    pub(crate) fn save(&self, conversation: &Conversation) -> Result<(), String> {
+        let path = self.conversation_path(&conversation.id)?;
+        let mut contents = String::new();
+        for turn in &conversation.turns {
+            if turn.conversation_id != conversation.id {
+                return Err(format!(
+                    "Turn {} belongs to conversation {}",
+                    turn.id, turn.conversation_id
+                ));
+            }
+            let line = serde_json::to_string(turn)
+                .map_err(|err| format!("Failed to serialize turn: {err}"))?;
+            contents.push_str(&line);
+            contents.push('\n');
+        }
+        let _guard = self.lock.lock().map_err(|_| "History store is poisoned")?;
+        fs::create_dir_all(&self.dir)
+            .map_err(|err| format!("Failed to create history dir: {err}"))?;
+        fs::write(&path, contents).map_err(|err| format!("Failed to write history: {err}"))?;
+        self.bump_revision();
+        Ok(())
+    }
+
+    pub(crate) fn contains(&self, id: &str) -> Result<bool, String> {
+        Ok(self.conversation_path(id)?.exists())
+    }
+
+    pub(crate) fn load(&self, id: &str) -> Result<Conversation, String> {
+        let path = self.conversation_path(id)?;
+        let _guard = self.lock.lock().map_err(|_| "History store is poisoned")?;
+        let contents = fs::read_to_string(&path)
+            .map_err(|err| format!("Failed to read conversation {id}: {err}"))?;
+        Ok(Conversation {
+            id: id.to_string(),
+            // Skip lines that fail to parse, e.g. one cut short by a crash.
+            turns: contents
+                .lines()
+                .filter_map(|line| serde_json::from_str(line).ok())
+                .collect(),
+        })
+    }
+
+    pub(crate) fn load_all(&self) -> Vec<Conversation> {
+        let Ok(entries) = fs::read_dir(&self.dir) else {
+            return Vec::new();
+        };
+        entries
+            .flatten()
+            .filter_map(|entry| {
+                let path = entry.path();
+                if path.extension().and_then(|ext| ext.to_str()) != Some("jsonl") {
+                    return None;
+                }
+                let id = path.file_stem()?.to_str()?.to_string();
+                self.load(&id).ok()
+            })
+            .collect()
+    }
+
+    pub(crate) fn delete(&self, id: &str) -> Result<(), String> {
+        let path = self.conversation_path(id)?;
+        let _guard = self.lock.lock().map_err(|_| "History store is poisoned")?;
+        fs::remove_file(&path)
+            .map_err(|err| format!("Failed to delete conversation {id}: {err}"))?;
+        self.bump_revision();
+        Ok(())
+    }
+}
+
+#[tauri::command]
+pub(crate) async fn list_conversations(
+    app: tauri::AppHandle,
+) -> Result<Vec<ConversationSummary>, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let store = app.state::<HistoryStore>();
+        let mut summaries: Vec<ConversationSummary> = store
+            .load_all()
+            .iter()
+            .filter_map(Conversation::summary)
+            .collect();
+        summaries.sort_by_key(|summary| std::cmp::Reverse(summary.updated_at));
+        Ok(summaries)
+    })
+    .await
+    .map_err(|err| format!("Failed to list conversations: {err}"))?
+}
+
+#[tauri::command]
+pub(crate) fn load_conversation(
+    store: tauri::State<'_, HistoryStore>,
+    id: String,
+) -> Result<Conversation, String> {
+    store.load(&id)
+}
+
+#[tauri::command]
+pub(crate) fn save_conversation(
+    store: tauri::State<'_, HistoryStore>,
+    conversation: Conversation,
+) -> Result<(), String> {
+    store.save(&conversation)
+}
+
+#[tauri::command]
+pub(crate) fn delete_conversation(
+    store: tauri::State<'_, HistoryStore>,
+    id: String,
+) -> Result<(), String> {
+    store.delete(&id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn civil_from_days_handles_leap_days_and_dates_before_the_epoch() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+        assert_eq!(civil_from_days(11_016), (2000, 2, 29));
+        assert_eq!(civil_from_days(19_782), (2024, 2, 29));
+        assert_eq!(civil_from_days(19_783), (2024, 3, 1));
+        // 1900 is not a leap year.
+        assert_eq!(civil_from_days(-25_508), (1900, 3, 1));
+    }
+
+    #[test]
+    fn utc_timestamp_formats_date_and_time() {
+        assert_eq!(utc_date(0), "1970-01-01");
+        assert_eq!(utc_timestamp(1_700_000_000_999), "2023-11-14 22:13:20 UTC");
+    }
+}
@@ -0,0 +1,510 @@
+use serde::Serialize;
+use std::fs;
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use tauri::Emitter;
+
+#[cfg(target_os = "windows")]
+use std::os::windows::process::CommandExt;
+
+use crate::{discovery, CopilotStatus};
+
+/// Stderr lines kept for the failure message.
+const STDERR_TAIL_LINES: usize = 5;
+
+/// A package manager invocation that installs or upgrades the Copilot CLI.
+pub(crate) struct PackageCommand {
+    /// Human-readable name such as `Homebrew`.
+    pub(crate) manager: &'static str,
+    pub(crate) program: &'static str,
+    pub(crate) args: Vec<String>,
+}
+
+#[derive(Clone, Serialize)]
+struct InstallOutputEvent {
+    /// `install` or `upgrade`.
+    action: &'static str,
+    /// `stdout` or `stderr`.
+    stream: &'static str,
+    line: String,
+}
+
+#[derive(Clone, Serialize)]
+pub(crate) struct InstallResult {
+    action: &'static str,
+    manager: &'static str,
+    success: bool,
+    exit_code: Option<i32>,
+    message: String,
+    /// Copilot status re-checked after the package manager exited.
+    status: Option<CopilotStatus>,
+    /// The re-check's `copilot --version` did not answer in time. The
+    /// binary was found, so this alone does not fail the install.
+    probe_timed_out: bool,
+}
+
+/// Package managers the Copilot CLI can be installed with.
+#[derive(Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum PackageManager {
+    Homebrew,
+    Winget,
+    Npm,
+}
+
+impl PackageManager {
+    fn label(self) -> &'static str {
+        match self {
+            PackageManager::Homebrew => "Homebrew",
+            PackageManager::Winget => "winget",
+            PackageManager::Npm => "npm",
+        }
+    }
+
+    /// Guesses which manager installed the binary at `path`, falling back to
+    /// the platform's default installer.
+    fn detect(path: Option<&Path>) -> Self {
+        // npm links its binaries into a bin dir, e.g. `/usr/local/bin/copilot`
+        // pointing into `lib/node_modules`; the link target tells more.
+        let resolved =
+            path.map(|path| fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf()));
+        let under_node_manager = resolved.as_ref().is_some_and(|resolved| {
+            discovery::node_manager_roots()
+                .into_iter()
+                .any(|root| resolved.starts_with(fs::canonicalize(&root).unwrap_or(root)))
+        });
+        let path = resolved
+            .map(|path| path.display().to_string().to_lowercase())
+            .unwrap_or_default();
+        if under_node_manager
+            || path.contains("node_modules")
+            || path.contains("npm")
+            || path.ends_with(".cmd")
+        {
+            PackageManager::Npm
+        } else if cfg!(target_os = "macos") {
+            PackageManager::Homebrew
+        } else if cfg!(target_os = "windows") {
+            PackageManager::Winget
+        } else {
+            PackageManager::Npm
+        }
+    }
+}
+
+#[derive(Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum UpdateState {
+    UpToDate,
+    UpdateAvailable,
+    /// `copilot --version` printed nothing that reads as a version, so the
+    /// installed one cannot be compared.
+    UnknownInstalledVersion,
+}
+
+#[derive(Serialize)]
+pub(crate) struct CopilotUpdate {
+    manager: PackageManager,
+    installed: Option<String>,
+    /// What `copilot --version` printed when it could not be parsed.
+    installed_raw: Option<String>,
+    latest: Option<String>,
+    state: UpdateState,
+    update_available: bool,
+}
+
+/// Pulls the first semver-looking word out of `raw`, e.g. `0.0.354` from
+/// `GitHub Copilot CLI 0.0.354.`.
+fn parse_version(raw: &str) -> Option<semver::Version> {
+    raw.split(|ch: char| ch.is_whitespace() || ch == ',' || ch == '@')
+        .map(|word| {
+            word.trim_start_matches('v')
+                .trim_end_matches(|ch: char| !ch.is_ascii_alphanumeric())
+        })
+        .find_map(|word| semver::Version::parse(word).ok())
+}
+
+/// Runs a short package manager query and returns its stdout.
+fn query(search_path: &str, program: &str, args: &[&str]) -> Result<String, String> {
+    let mut command = std::process::Command::new(program);
+    command.args(args);
+    command.env("PATH", search_path);
+    #[cfg(target_os = "windows")]
+    {
+        command.creation_flags(crate::CREATE_NO_WINDOW);
+    }
+    command.stdin(Stdio::null());
+    let output = command
+        .output()
+        .map_err(|err| format!("Failed to run {program}: {err}"))?;
+    if !output.status.success() {
+        return Err(format!(
+            "{program} failed: {}",
+            crate::decode_platform_bytes(&output.stderr).trim()
+        ));
+    }
+    Ok(crate::decode_platform_bytes(&output.stdout))
+}
+
+/// Asks `manager`, looked up on `search_path`, for the newest published
+/// Copilot CLI version.
+fn latest_version(manager: PackageManager, search_path: &str) -> Result<semver::Version, String> {
+    let raw = match manager {
+        PackageManager::Homebrew => {
+            let info: serde_json::Value = serde_json::from_str(&query(
+                search_path,
+                "brew",
+                &["info", "--json=v2", "copilot-cli"],
+            )?)
+            .map_err(|err| format!("Failed to parse brew info: {err}"))?;
+            // copilot-cli may be packaged as a formula or a cask.
+            info.pointer("/formulae/0/versions/stable")
+                .or_else(|| info.pointer("/casks/0/version"))
+                .and_then(|value| value.as_str())
+                .unwrap_or_default()
+                .to_string()
+        }
+        PackageManager::Winget => query(
+            search_path,
+            "winget",
+            &[
+                "show",
+                "--id",
+                "GitHub.Copilot",
+                "--exact",
+                "--disable-interactivity",
+            ],
+        )?
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("Version:").map(str::to_string))
+        .unwrap_or_default(),
+        PackageManager::Npm => query(search_path, "npm", &["view", "@github/copilot", "version"])?,
+    };
+    parse_version(&raw)
+        .ok_or_else(|| format!("{} reported no version for Copilot CLI", manager.label()))
+}
+
+/// npm prefix the binary at `path` was installed under: `<prefix>/bin/copilot`
+/// on Unix, `<prefix>\copilot.cmd` on Windows.
+fn npm_prefix(path: Option<&Path>) -> Option<PathBuf> {
+    let dir = path?.parent()?;
+    if dir.file_name().is_some_and(|name| name == "bin") {
+        dir.parent().map(Path::to_path_buf)
+    } else {
+        Some(dir.to_path_buf())
+    }
+}
+
+/// Whether this user can write where npm installs global packages under
+/// `prefix`. Creates and removes a probe file, since permission bits alone
+/// miss ownership and ACLs.
+fn npm_prefix_writable(prefix: &Path) -> bool {
+    let modules = if cfg!(target_os = "windows") {
+        prefix.join("node_modules")
+    } else {
+        prefix.join("lib").join("node_modules")
+    };
+    let dir = if modules.is_dir() {
+        modules
+    } else {
+        prefix.to_path_buf()
+    };
+    let probe = dir.join(format!(".ghc-write-test-{}", crate::history::new_id()));
+    match fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&probe)
+    {
+        Ok(_) => {
+            let _ = fs::remove_file(&probe);
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+fn upgrade_command(manager: PackageManager, path: Option<&Path>) -> PackageCommand {
+    let args = match manager {
+        PackageManager::Homebrew => vec!["upgrade".into(), "copilot-cli".into()],
+        PackageManager::Winget => [
+            "upgrade",
+            "--id",
+            "GitHub.Copilot",
+            "--silent",
+            "--accept-package-agreements",
+            "--accept-source-agreements",
+        ]
+        .map(String::from)
+        .to_vec(),
+        PackageManager::Npm => {
+            let mut args = vec!["install".to_string(), "--global".to_string()];
+            if let Some(prefix) = npm_prefix(path) {
+                args.push("--prefix".into());
+                args.push(prefix.display().to_string());
+            }
+            args.push("@github/copilot@latest".into());
+            args
+        }
+    };
+    PackageCommand {
+        manager: manager.label(),
+        program: match manager {
+            PackageManager::Homebrew => "brew",
+            PackageManager::Winget => "winget",
+            PackageManager::Npm => "npm",
+        },
+        args,
+    }
+}
+
+/// How the Copilot CLI is installed on this platform.
+fn install_command() -> Result<PackageCommand, String> {
+    if cfg!(target_os = "macos") {
+        return Ok(PackageCommand {
+            manager: "Homebrew",
+            program: "brew",
+            args: vec!["install".into(), "copilot-cli".into()],
+        });
+    }
+    if cfg!(target_os = "windows") {
+        return Ok(PackageCommand {
+            manager: "winget",
+            program: "winget",
+            args: [
+                "install",
+                "--id",
+                "GitHub.Copilot",
+                "--silent",
+                "--accept-package-agreements",
+                "--accept-source-agreements",
+            ]
+            .map(String::from)
+            .to_vec(),
+        });
+    }
+    #[cfg(target_os = "linux")]
+    {
+        let prefix = discovery::linux_user_prefix()
+            .ok_or_else(|| "Missing HOME environment variable".to_string())?;
+        Ok(PackageCommand {
+            manager: "npm",
+            program: "npm",
+            args: vec![
+                "install".into(),
+                "--global".into(),
+                "--prefix".into(),
+                prefix.display().to_string(),
+                "@github/copilot".into(),
+            ],
+        })
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        Err("Copilot install is only supported on macOS, Windows and Linux.".to_string())
+    }
+}
+
+fn missing_manager_message(manager: &str) -> String {
+    match manager {
+        "Homebrew" => "Homebrew not found. Install it from https://brew.sh.".to_string(),
+        "winget" => "winget not found. Install App Installer from the Microsoft Store.".to_string(),
+        "npm" => "npm not found. Install Node.js from https://nodejs.org or your package manager."
+            .to_string(),
+        _ => format!("{manager} not found."),
+    }
+}
+
+/// Emits each line `reader` produces as a `copilot-install-output` event and
+/// returns the last few lines.
+fn forward_lines(
+    app: &tauri::AppHandle,
+    action: &'static str,
+    stream: &'static str,
+    reader: impl Read,
+) -> Vec<String> {
+    let mut reader = BufReader::new(reader);
+    let mut buffer = Vec::new();
+    let mut tail = Vec::new();
+    loop {
+        buffer.clear();
+        match reader.read_until(b'\n', &mut buffer) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {}
+        }
+        let line = crate::decode_platform_bytes(&buffer).trim_end().to_string();
+        if line.is_empty() {
+            continue;
+        }
+        let _ = app.emit(
+            "copilot-install-output",
+            InstallOutputEvent {
+                action,
+                stream,
+                line: line.clone(),
+            },
+        );
+        tail.push(line);
+        if tail.len() > STDERR_TAIL_LINES {
+            tail.remove(0);
+        }
+    }
+    tail
+}
+
+/// Runs `package`, streaming its output, then re-checks the Copilot status
+/// and emits the outcome as `copilot-install-done`.
+pub(crate) fn run_streamed(
+    app: &tauri::AppHandle,
+    action: &'static str,
+    package: PackageCommand,
+) -> Result<InstallResult, String> {
+    let mut command = std::process::Command::new(package.program);
+    command.args(&package.args);
+    command.env(
+        "PATH",
+        discovery::augmented_path(&crate::settings::current(app)),
+    );
+    #[cfg(target_os = "windows")]
+    {
+        command.creation_flags(crate::CREATE_NO_WINDOW);
+    }
+    command.stdin(Stdio::null());
+    command.stdout(Stdio::piped());
+    command.stderr(Stdio::piped());
+
+    let mut child = command.spawn().map_err(|err| match err.kind() {
+        std::io::ErrorKind::NotFound => missing_manager_message(package.manager),
+        _ => format!("Failed to run {}: {err}", package.program),
+    })?;
+
+    let stderr_reader = child.stderr.take().map(|stderr| {
+        let app = app.clone();
+        std::thread::spawn(move || forward_lines(&app, action, "stderr", stderr))
+    });
+    if let Some(stdout) = child.stdout.take() {
+        forward_lines(app, action, "stdout", stdout);
+    }
+    let stderr_tail = stderr_reader
+        .and_then(|handle| handle.join().ok())
+        .unwrap_or_default();
+    let exit = child
+        .wait()
+        .map_err(|err| format!("Failed to wait for {}: {err}", package.program))?;
+
+    let probe = crate::copilot_status(app);
+    let probe_timeout = match &probe {
+        Err(crate::CopilotError::Timeout { seconds, .. }) => Some(*seconds),
+        _ => None,
+    };
+    let status = probe.ok();
+    let resolvable = status.as_ref().is_some_and(|status| status.installed);
+    let past = if action == "upgrade" {
+        "upgraded"
+    } else {
+        "installed"
+    };
+    let message = if !exit.success() {
+        let detail = stderr_tail.join("\n");
+        if detail.is_empty() {
+            format!("{} exited with {exit}.", package.manager)
+        } else {
+            format!("{} exited with {exit}: {detail}", package.manager)
+        }
+    } else if resolvable {
+        format!("Copilot CLI {past} via {}.", package.manager)
+    } else if let Some(seconds) = probe_timeout {
+        format!(
+            "Copilot CLI {past} via {}, but `copilot --version` did not answer within {seconds}s. It may still be finishing its first-run setup.",
+            package.manager
+        )
+    } else {
+        format!(
+            "Copilot CLI {past} via {}, but the copilot binary could not be found yet. Restart the app or set copilot_path in settings.",
+            package.manager
+        )
+    };
+    let result = InstallResult {
+        action,
+        manager: package.manager,
+        success: exit.success() && (resolvable || probe_timeout.is_some()),
+        exit_code: exit.code(),
+        message,
+        status,
+        probe_timed_out: probe_timeout.is_some(),
+    };
+    if exit.success() {
+        // The model list cached while Copilot was missing or older came
+        // from the bundled catalog.
+        crate::models::clear_cache(app);
+    }
+    if !result.success {
+        crate::diagnostics::record_error(app, action, &result.message);
+    }
+    let _ = app.emit("copilot-install-done", result.clone());
+    Ok(result)
+}
+
+#[tauri::command]
+pub(crate) async fn install_copilot_cli(app: tauri::AppHandle) -> Result<InstallResult, String> {
+    tauri::async_runtime::spawn_blocking(move || run_streamed(&app, "install", install_command()?))
+        .await
+        .map_err(|err| format!("Failed to install copilot: {err}"))?
+}
+
+/// Compares the installed Copilot CLI with the newest version its package
+/// manager offers.
+#[tauri::command]
+pub(crate) async fn check_copilot_update(app: tauri::AppHandle) -> Result<CopilotUpdate, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let status =
+            crate::copilot_status(&app).map_err(|_| "Copilot did not respond".to_string())?;
+        let path = status.path.as_deref().map(Path::new);
+        let manager = PackageManager::detect(path);
+        let installed = status.version.as_deref().and_then(parse_version);
+        let search_path = discovery::augmented_path(&crate::settings::current(&app));
+        let latest = latest_version(manager, &search_path)?;
+        let state = match &installed {
+            None => UpdateState::UnknownInstalledVersion,
+            Some(installed) if *installed < latest => UpdateState::UpdateAvailable,
+            Some(_) => UpdateState::UpToDate,
+        };
+        Ok(CopilotUpdate {
+            manager,
+            installed_raw: installed.is_none().then_some(status.version).flatten(),
+            installed: installed.map(|version| version.to_string()),
+            latest: Some(latest.to_string()),
+            state,
+            update_available: state == UpdateState::UpdateAvailable,
+        })
+    })
+    .await
+    .map_err(|err| format!("Failed to check for updates: {err}"))?
+}
+
+/// Upgrades the Copilot CLI with the manager that installed it, streaming
+/// progress like [`install_copilot_cli`].
+#[tauri::command]
+pub(crate) async fn upgrade_copilot_cli(app: tauri::AppHandle) -> Result<InstallResult, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let settings = crate::settings::current(&app);
+        let path = discovery::resolve(&settings).path;
+        let manager = PackageManager::detect(path.as_deref());
+        if manager == PackageManager::Npm {
+            // A root-owned global npm would fail with EACCES halfway through.
+            if let Some(prefix) = npm_prefix(path.as_deref())
+                .filter(|prefix| !npm_prefix_writable(prefix))
+            {
+                let message = format!(
+                    "Copilot CLI was installed with npm under {}, which this user cannot write to. Upgrade it from a terminal with the rights that installed it, e.g. `sudo npm install -g @github/copilot@latest`.",
+                    prefix.display()
+                );
+                crate::diagnostics::record_error(&app, "upgrade", &message);
+                return Err(message);
+            }
+        }
+        run_streamed(&app, "upgrade", upgrade_command(manager, path.as_deref()))
+    })
+    .await
+    .map_err(|err| format!("Failed to upgrade copilot: {err}"))?
+}
@@ -1,14 +1,37 @@
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::env;
 use std::fs;
+use std::io::{BufRead, BufReader, Read};
 use std::path::PathBuf;
 use std::process::Stdio;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
-use tauri::Emitter;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::{Emitter, Manager};
+
+mod accounts;
+mod context;
+mod conversation;
+mod credentials;
+mod diagnostics;
+mod discovery;
+mod export;
+mod github;
+mod history;
+mod installer;
+mod login;
+mod models;
+mod search;
+mod settings;
+mod templates;
+mod usage;
 
 #[cfg(target_os = "windows")]
 use encoding_rs::EUC_KR;
+#[cfg(unix)]
+use std::os::unix::process::CommandExt as UnixCommandExt;
 #[cfg(target_os = "windows")]
 use std::os::windows::process::CommandExt;
 
@@ -22,108 +45,613 @@ fn greet(name: &str) -> String {
 
 #[derive(Serialize)]
 struct CopilotResult {
+    conversation_id: String,
+    turn_id: String,
     output: String,
-    temp_path: Option<String>,
-    context_path: Option<String>,
+    exit_code: Option<i32>,
+    /// Earlier turns of the conversation replayed into the prompt.
+    prior_turns: usize,
+    staging_dir: Option<String>,
+    context_paths: Vec<String>,
+    context_report: Option<context::ContextReport>,
+    /// Set when the run went past the monthly soft limit.
+    budget_warning: Option<String>,
 }
 
-#[derive(Serialize)]
+#[derive(Clone, Serialize)]
 struct CopilotStatus {
     installed: bool,
     version: Option<String>,
     path: Option<String>,
+    /// Which step of the resolution chain found `path`.
+    rule: Option<discovery::ResolutionRule>,
+    /// Every path examined, in order.
+    candidates: Vec<discovery::Candidate>,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum CopilotError {
+    Cancelled,
+    /// The child was killed by the watchdog. `idle` is set when it stopped
+    /// producing output rather than exceeding the overall limit.
+    Timeout {
+        seconds: u64,
+        idle: bool,
+    },
+    /// The attached context violated the configured context policy.
+    ContextRefused {
+        reason: String,
+        report: context::ContextReport,
+    },
+    /// The requested model is not one Copilot offers.
+    UnknownModel {
+        model: String,
+        available: Vec<String>,
+    },
+    /// The monthly hard limit on premium requests is used up.
+    BudgetExceeded {
+        used: f64,
+        limit: f64,
+    },
+    /// Copilot ran but exited with a non-zero status.
+    Exited {
+        exit_code: Option<i32>,
+        message: String,
+    },
+    Failed {
+        message: String,
+    },
+}
+
+impl From<String> for CopilotError {
+    fn from(message: String) -> Self {
+        CopilotError::Failed { message }
+    }
+}
+
+impl From<context::StageError> for CopilotError {
+    fn from(err: context::StageError) -> Self {
+        match err {
+            context::StageError::Refused(report) => CopilotError::ContextRefused {
+                reason: report.refused.clone().unwrap_or_default(),
+                report,
+            },
+            context::StageError::Failed(message) => CopilotError::Failed { message },
+        }
+    }
 }
 
+struct ActiveRun {
+    pid: u32,
+    cancelled: Arc<AtomicBool>,
+    staging_dir: Option<PathBuf>,
+}
+
+/// Copilot child processes that are still running, keyed by request id.
+#[derive(Default)]
+struct CopilotRuns(Mutex<HashMap<String, ActiveRun>>);
+
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct RunCopilotArgs {
+    request_id: String,
+    /// Conversation the turn is recorded under; a new one is started when
+    /// omitted.
+    conversation_id: Option<String>,
     prompt: String,
+    /// The `default_model` setting when empty.
+    #[serde(default)]
     model: String,
-    context_path: Option<String>,
+    #[serde(default)]
+    context_paths: Vec<String>,
+    /// GitHub account whose token the run uses; the active account when
+    /// omitted.
+    #[serde(default)]
+    account: Option<String>,
 }
 
 #[tauri::command]
-async fn run_copilot(args: RunCopilotArgs) -> Result<CopilotResult, String> {
+async fn run_copilot(
+    app: tauri::AppHandle,
+    mut args: RunCopilotArgs,
+) -> Result<CopilotResult, CopilotError> {
     tauri::async_runtime::spawn_blocking(move || {
-        let token = resolve_github_token();
-        let mut command = copilot_command();
-        let mut temp_path: Option<String> = None;
-        let context_path_for_debug = args.context_path.clone();
-        let full_prompt = match args.context_path.as_ref() {
-            Some(path) if !path.trim().is_empty() => {
-                let path_buf = PathBuf::from(path);
-                let file_name = path_buf
-                    .file_name()
-                    .and_then(|name| name.to_str())
-                    .unwrap_or(path);
-                let temp_root = env::temp_dir().join("ghc");
-                fs::create_dir_all(&temp_root)
-                    .map_err(|err| format!("Failed to create temp dir: {err}"))?;
-                let stamp = SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .map_err(|err| format!("Failed to generate temp name: {err}"))?
-                    .as_millis();
-                let temp_name = format!(".copilot-context-{stamp}-{file_name}");
-                let temp_file = temp_root.join(&temp_name);
-                fs::copy(&path_buf, &temp_file)
-                    .map_err(|err| format!("Failed to copy context file: {err}"))?;
-                temp_path = Some(temp_file.display().to_string());
-                format!("{} {}", args.prompt, temp_file.display())
-            }
-            _ => args.prompt,
+        if args.model.trim().is_empty() {
+            args.model = settings::current(&app).default_model;
+        }
+        let conversation_id = args.conversation_id.clone().unwrap_or_else(history::new_id);
+        let turn = history::Turn {
+            id: args.request_id.clone(),
+            conversation_id: conversation_id.clone(),
+            prompt: args.prompt.clone(),
+            model: args.model.clone(),
+            account: args.account.clone(),
+            context_files: args
+                .context_paths
+                .iter()
+                .filter_map(|path| {
+                    PathBuf::from(path)
+                        .file_name()
+                        .map(|name| name.to_string_lossy().to_string())
+                })
+                .collect(),
+            output: String::new(),
+            started_at: history::now_millis(),
+            finished_at: 0,
+            status: history::TurnStatus::Success,
+            exit_code: None,
+            error: None,
         };
-        command
-            .arg("-s")
-            .arg("-p")
-            .arg(full_prompt)
-            .arg("--model")
-            .arg(args.model);
-        if let Some(token) = token {
-            command.env("GITHUB_TOKEN", token);
+        let result = execute_copilot(&app, conversation_id, args);
+        record_turn(&app, turn, &result);
+        result
+    })
+    .await
+    .map_err(|err| format!("Failed to run copilot: {err}"))?
+}
+
+/// Saves the outcome of a `run_copilot` call to the history store.
+fn record_turn(
+    app: &tauri::AppHandle,
+    mut turn: history::Turn,
+    result: &Result<CopilotResult, CopilotError>,
+) {
+    turn.finished_at = history::now_millis();
+    match result {
+        Ok(result) => {
+            turn.output = result.output.clone();
+            turn.exit_code = result.exit_code;
+        }
+        Err(CopilotError::Cancelled) => turn.status = history::TurnStatus::Cancelled,
+        Err(CopilotError::Timeout { seconds, idle }) => {
+            turn.status = history::TurnStatus::Timeout;
+            turn.error = Some(if *idle {
+                format!("No output for {seconds}s")
+            } else {
+                format!("Did not finish within {seconds}s")
+            });
+        }
+        Err(CopilotError::ContextRefused { reason, .. }) => {
+            turn.status = history::TurnStatus::Refused;
+            turn.error = Some(reason.clone());
+        }
+        Err(CopilotError::UnknownModel { model, .. }) => {
+            turn.status = history::TurnStatus::Error;
+            turn.error = Some(format!("Unknown model: {model}"));
+        }
+        Err(CopilotError::BudgetExceeded { used, limit }) => {
+            turn.status = history::TurnStatus::Refused;
+            turn.error = Some(format!(
+                "Monthly budget of {limit} premium requests reached ({used} used)"
+            ));
+        }
+        Err(CopilotError::Exited { exit_code, message }) => {
+            turn.status = history::TurnStatus::Failed;
+            turn.exit_code = *exit_code;
+            turn.error = Some(message.clone());
+        }
+        Err(CopilotError::Failed { message }) => {
+            turn.status = history::TurnStatus::Error;
+            turn.error = Some(message.clone());
+        }
+    }
+    if let Some(error) = turn.error.as_deref() {
+        diagnostics::record_error(app, "copilot", error);
+    }
+    usage::record(app, &turn);
+    let _ = app.state::<history::HistoryStore>().append_turn(&turn);
+}
+
+fn execute_copilot(
+    app: &tauri::AppHandle,
+    conversation_id: String,
+    args: RunCopilotArgs,
+) -> Result<CopilotResult, CopilotError> {
+    let models = models::available(app);
+    if !models.contains(&args.model) {
+        return Err(CopilotError::UnknownModel {
+            model: args.model,
+            available: models.models.into_iter().map(|model| model.id).collect(),
+        });
+    }
+    let budget_warning = match usage::check_budget(app, &args.model, args.account.as_deref()) {
+        usage::BudgetCheck::Allowed => None,
+        usage::BudgetCheck::Warn { used, limit } => Some(format!(
+            "{used} of {limit} premium requests used this month."
+        )),
+        usage::BudgetCheck::Block { used, limit } => {
+            return Err(CopilotError::BudgetExceeded { used, limit });
+        }
+    };
+    let token = resolve_github_token(app, args.account.as_deref())?;
+    let mut command = copilot_command(app);
+    let settings = settings::current(app);
+    let staged = context::stage_context(
+        &args.request_id,
+        &args.context_paths,
+        &settings.context_policy,
+        &context::temp_root(&settings.temp_dir_name),
+    )?;
+    let assembled = conversation::assemble_prompt(
+        &app.state::<history::HistoryStore>(),
+        &conversation_id,
+        &args.prompt,
+        &conversation::TurnBudget {
+            max_turns: settings.conversation_max_turns,
+            max_chars: settings.conversation_max_chars,
+        },
+    );
+    let full_prompt = match staged.as_ref() {
+        Some(staged) => {
+            let mut prompt = assembled.prompt;
+            for entry in &staged.entries {
+                prompt.push(' ');
+                prompt.push_str(&entry.display().to_string());
+            }
+            prompt
+        }
+        None => assembled.prompt,
+    };
+    command
+        .arg("-s")
+        .arg("-p")
+        .arg(full_prompt)
+        .arg("--model")
+        .arg(args.model);
+    if let Some(token) = token {
+        command.env("GITHUB_TOKEN", token);
+    }
+    let github = github::GitHubConfig::resolve(app);
+    if github.is_enterprise() {
+        // The Copilot CLI authenticates against github.com unless told
+        // otherwise.
+        command.env("GH_HOST", &github.host);
+    }
+    command.stdin(Stdio::null());
+    command.stdout(Stdio::piped());
+    command.stderr(Stdio::piped());
+
+    let deadlines = Deadlines::from_secs(
+        settings.copilot_timeout_secs,
+        settings.copilot_idle_timeout_secs,
+    );
+    let output = stream_copilot_output(
+        app,
+        &args.request_id,
+        command,
+        staged.as_ref().map(|staged| staged.root.clone()),
+        deadlines,
+    );
+    if let Some(ref staged) = staged {
+        staged.cleanup();
+    }
+    let output = output?;
+
+    if output.cancelled {
+        Err(CopilotError::Cancelled)
+    } else if let Some(timeout) = output.timeout {
+        Err(timeout)
+    } else if output.success {
+        Ok(CopilotResult {
+            conversation_id,
+            turn_id: args.request_id,
+            output: output.stdout.trim().to_string(),
+            exit_code: output.exit_code,
+            prior_turns: assembled.prior_turns,
+            staging_dir: staged
+                .as_ref()
+                .map(|staged| staged.root.display().to_string()),
+            context_paths: args.context_paths,
+            context_report: staged.map(|staged| staged.report),
+            budget_warning,
+        })
+    } else {
+        Err(CopilotError::Exited {
+            exit_code: output.exit_code,
+            message: output.stderr.trim().to_string(),
+        })
+    }
+}
+
+/// Reports how the context policy would treat `paths` without running
+/// Copilot.
+#[tauri::command]
+async fn inspect_context(
+    app: tauri::AppHandle,
+    paths: Vec<String>,
+) -> Result<context::ContextReport, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let policy = settings::current(&app).context_policy;
+        context::inspect_context(&paths, &policy)
+    })
+    .await
+    .map_err(|err| format!("Failed to inspect context: {err}"))?
+}
+
+#[tauri::command]
+fn cancel_copilot(state: tauri::State<'_, CopilotRuns>, request_id: String) -> Result<(), String> {
+    let runs = state
+        .0
+        .lock()
+        .map_err(|_| "Copilot run registry is poisoned".to_string())?;
+    let run = runs
+        .get(&request_id)
+        .ok_or_else(|| format!("No running copilot request with id {request_id}"))?;
+    run.cancelled.store(true, Ordering::SeqCst);
+    kill_process_tree(run.pid)?;
+    if let Some(ref dir) = run.staging_dir {
+        let _ = fs::remove_dir_all(dir);
+    }
+    Ok(())
+}
+
+#[derive(Clone, Serialize)]
+struct CopilotOutputEvent {
+    request_id: String,
+    chunk: String,
+}
+
+#[derive(Clone, Serialize)]
+struct CopilotDoneEvent {
+    request_id: String,
+    success: bool,
+    cancelled: bool,
+    timed_out: bool,
+    exit_code: Option<i32>,
+    stderr: String,
+}
+
+struct StreamedOutput {
+    success: bool,
+    exit_code: Option<i32>,
+    cancelled: bool,
+    timeout: Option<CopilotError>,
+    stdout: String,
+    stderr: String,
+}
+
+const WATCHDOG_TICK: Duration = Duration::from_millis(100);
+
+/// Limits enforced on a copilot child process. `None` disables a limit.
+#[derive(Clone, Copy)]
+struct Deadlines {
+    total: Option<Duration>,
+    idle: Option<Duration>,
+}
+
+impl Deadlines {
+    fn from_secs(total: u64, idle: u64) -> Self {
+        let limit = |secs: u64| (secs > 0).then(|| Duration::from_secs(secs));
+        Deadlines {
+            total: limit(total),
+            idle: limit(idle),
         }
+    }
+}
 
-        let temp_path_for_result = temp_path.clone();
-        let output = match command.output() {
-            Ok(output) => output,
-            Err(err) => {
-                if let Some(ref path) = temp_path {
-                    let _ = fs::remove_file(path);
+/// Background thread that kills a child's process tree once one of its
+/// [`Deadlines`] passes.
+struct Watchdog {
+    last_activity: Arc<Mutex<Instant>>,
+    finished: Arc<AtomicBool>,
+    handle: std::thread::JoinHandle<Option<CopilotError>>,
+}
+
+impl Watchdog {
+    fn spawn(pid: u32, deadlines: Deadlines) -> Self {
+        let started = Instant::now();
+        let last_activity = Arc::new(Mutex::new(started));
+        let finished = Arc::new(AtomicBool::new(false));
+        let activity = last_activity.clone();
+        let done = finished.clone();
+        let handle = std::thread::spawn(move || {
+            while !done.load(Ordering::SeqCst) {
+                let now = Instant::now();
+                let idle_for = activity
+                    .lock()
+                    .map(|last| now.duration_since(*last))
+                    .unwrap_or_default();
+                let tripped = match (deadlines.total, deadlines.idle) {
+                    (Some(limit), _) if now.duration_since(started) >= limit => {
+                        Some(CopilotError::Timeout {
+                            seconds: limit.as_secs(),
+                            idle: false,
+                        })
+                    }
+                    (_, Some(limit)) if idle_for >= limit => Some(CopilotError::Timeout {
+                        seconds: limit.as_secs(),
+                        idle: true,
+                    }),
+                    _ => None,
+                };
+                if tripped.is_some() {
+                    let _ = kill_process_tree(pid);
+                    return tripped;
                 }
-                return Err(format!("Failed to run copilot: {err}"));
+                std::thread::park_timeout(WATCHDOG_TICK);
             }
-        };
-        if let Some(ref path) = temp_path {
-            let _ = fs::remove_file(path);
+            None
+        });
+        Watchdog {
+            last_activity,
+            finished,
+            handle,
         }
+    }
 
-        if output.status.success() {
-            Ok(CopilotResult {
-                output: String::from_utf8_lossy(&output.stdout).trim().to_string(),
-                temp_path: temp_path_for_result,
-                context_path: context_path_for_debug,
-            })
-        } else {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            Err(stderr.trim().to_string())
+    /// Records that the child produced output, resetting the idle timer.
+    fn touch(&self) {
+        if let Ok(mut last) = self.last_activity.lock() {
+            *last = Instant::now();
         }
+    }
+
+    /// Stops watching and reports the timeout that fired, if any.
+    fn finish(self) -> Option<CopilotError> {
+        self.finished.store(true, Ordering::SeqCst);
+        self.handle.thread().unpark();
+        self.handle.join().ok().flatten()
+    }
+}
+
+/// Runs a short copilot probe, killing it if it exceeds `timeout_secs`.
+fn probe_output(
+    mut command: std::process::Command,
+    timeout_secs: u64,
+) -> Result<std::process::Output, CopilotError> {
+    command.stdin(Stdio::null());
+    command.stdout(Stdio::piped());
+    command.stderr(Stdio::piped());
+    let child = command
+        .spawn()
+        .map_err(|err| format!("Failed to run copilot: {err}"))?;
+    let watchdog = Watchdog::spawn(child.id(), Deadlines::from_secs(timeout_secs, 0));
+    let output = child.wait_with_output();
+    if let Some(timeout) = watchdog.finish() {
+        return Err(timeout);
+    }
+    output.map_err(|err| format!("Failed to run copilot: {err}").into())
+}
+
+/// Spawns `command` and forwards its stdout to the webview line by line as
+/// `copilot-output` events, followed by a single `copilot-done` event.
+///
+/// While the child is alive it is registered in [`CopilotRuns`] so that
+/// `cancel_copilot` can kill it and remove `staging_dir`, and a [`Watchdog`]
+/// enforces `deadlines`.
+fn stream_copilot_output(
+    app: &tauri::AppHandle,
+    request_id: &str,
+    mut command: std::process::Command,
+    staging_dir: Option<PathBuf>,
+    deadlines: Deadlines,
+) -> Result<StreamedOutput, String> {
+    let mut child = command
+        .spawn()
+        .map_err(|err| format!("Failed to run copilot: {err}"))?;
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let runs = app.state::<CopilotRuns>();
+    if let Ok(mut runs) = runs.0.lock() {
+        runs.insert(
+            request_id.to_string(),
+            ActiveRun {
+                pid: child.id(),
+                cancelled: cancelled.clone(),
+                staging_dir,
+            },
+        );
+    }
+    let watchdog = Watchdog::spawn(child.id(), deadlines);
+    let stdout = child.stdout.take();
+    let stderr_reader = child.stderr.take().map(|mut stderr| {
+        std::thread::spawn(move || {
+            let mut bytes = Vec::new();
+            let _ = stderr.read_to_end(&mut bytes);
+            String::from_utf8_lossy(&bytes).to_string()
+        })
+    });
+
+    let mut collected = String::new();
+    if let Some(stdout) = stdout {
+        let mut reader = BufReader::new(stdout);
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            match reader.read_until(b'\n', &mut line) {
+                Ok(0) => break,
+                Ok(_) => {
+                    watchdog.touch();
+                    let chunk = String::from_utf8_lossy(&line).to_string();
+                    collected.push_str(&chunk);
+                    let _ = app.emit(
+                        "copilot-output",
+                        CopilotOutputEvent {
+                            request_id: request_id.to_string(),
+                            chunk,
+                        },
+                    );
+                }
+                Err(_) => break,
+            }
+        }
+    }
+
+    let status = child.wait();
+    let timeout = watchdog.finish();
+    if let Ok(mut runs) = runs.0.lock() {
+        runs.remove(request_id);
+    }
+    let status = status.map_err(|err| format!("Failed to run copilot: {err}"))?;
+    let stderr = stderr_reader
+        .and_then(|handle| handle.join().ok())
+        .unwrap_or_default();
+    let cancelled = cancelled.load(Ordering::SeqCst);
+
+    let _ = app.emit(
+        "copilot-done",
+        CopilotDoneEvent {
+            request_id: request_id.to_string(),
+            success: status.success() && !cancelled && timeout.is_none(),
+            cancelled,
+            timed_out: timeout.is_some(),
+            exit_code: status.code(),
+            stderr: stderr.clone(),
+        },
+    );
+
+    Ok(StreamedOutput {
+        success: status.success(),
+        exit_code: status.code(),
+        cancelled,
+        timeout,
+        stdout: collected,
+        stderr,
     })
-    .await
-    .map_err(|err| format!("Failed to run copilot: {err}"))?
+}
+
+fn kill_process_tree(pid: u32) -> Result<(), String> {
+    #[cfg(target_os = "windows")]
+    {
+        let mut cmd = std::process::Command::new("taskkill");
+        cmd.args(["/PID", &pid.to_string(), "/T", "/F"]);
+        cmd.creation_flags(CREATE_NO_WINDOW);
+        cmd.stdin(Stdio::null());
+        cmd.stdout(Stdio::null());
+        cmd.stderr(Stdio::null());
+        cmd.status()
+            .map_err(|err| format!("Failed to run taskkill: {err}"))?;
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    {
+        // The child leads its own process group, so a negative pid targets
+        // the whole tree.
+        std::process::Command::new("kill")
+            .args(["-KILL", "--", &format!("-{pid}")])
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .map_err(|err| format!("Failed to run kill: {err}"))?;
+    }
+
+    Ok(())
 }
 
 #[tauri::command]
-async fn get_copilot_version() -> Result<String, String> {
+async fn get_copilot_version(app: tauri::AppHandle) -> Result<String, CopilotError> {
     tauri::async_runtime::spawn_blocking(move || {
-        let output = copilot_command()
-            .arg("--version")
-            .output()
-            .map_err(|err| format!("Failed to run copilot: {err}"))?;
+        let timeout_secs = settings::current(&app).probe_timeout_secs;
+        let mut command = copilot_command(&app);
+        command.arg("--version");
+        let output = probe_output(command, timeout_secs)?;
 
         if output.status.success() {
             Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
         } else {
             let stderr = String::from_utf8_lossy(&output.stderr);
-            Err(stderr.trim().to_string())
+            Err(stderr.trim().to_string().into())
         }
     })
     .await
@@ -131,29 +659,37 @@ async fn get_copilot_version() -> Result<String, String> {
 }
 
 #[tauri::command]
-async fn get_copilot_status() -> Result<CopilotStatus, String> {
-    tauri::async_runtime::spawn_blocking(move || {
-        if let Ok(output) = copilot_command().arg("--version").output() {
-            if output.status.success() {
-                let version = String::from_utf8_lossy(&output.stdout).trim().to_string();
-                let path = resolve_copilot_path().map(|path| path.display().to_string());
-                return Ok(CopilotStatus {
-                    installed: true,
-                    version: Some(version),
-                    path,
-                });
-            }
-        }
+async fn get_copilot_status(app: tauri::AppHandle) -> Result<CopilotStatus, CopilotError> {
+    tauri::async_runtime::spawn_blocking(move || copilot_status(&app))
+        .await
+        .map_err(|err| format!("Failed to check copilot: {err}"))?
+}
 
-        let path = resolve_copilot_path().map(|path| path.display().to_string());
-        Ok(CopilotStatus {
-            installed: path.is_some(),
-            version: None,
-            path,
-        })
+fn copilot_status(app: &tauri::AppHandle) -> Result<CopilotStatus, CopilotError> {
+    let settings = settings::current(app);
+    let resolution = discovery::resolve(&settings);
+    let path = resolution
+        .path
+        .as_ref()
+        .map(|path| path.display().to_string());
+    let mut command = copilot_command_for(&settings, resolution.path.clone());
+    command.arg("--version");
+    let probe = probe_output(command, settings.probe_timeout_secs);
+    if let Err(timeout @ CopilotError::Timeout { .. }) = probe {
+        return Err(timeout);
+    }
+    let version = probe
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string());
+
+    Ok(CopilotStatus {
+        installed: version.is_some() || path.is_some(),
+        version,
+        path,
+        rule: resolution.rule,
+        candidates: resolution.candidates,
     })
-    .await
-    .map_err(|err| format!("Failed to check copilot: {err}"))?
 }
 
 #[tauri::command]
@@ -185,7 +721,7 @@ async fn get_copilot_where_log() -> Result<String, String> {
             if log.is_empty() {
                 log.push_str("No output from where copilot");
             }
-            return Ok(log.trim_end().to_string());
+            Ok(log.trim_end().to_string())
         }
 
         #[cfg(not(target_os = "windows"))]
@@ -209,7 +745,7 @@ async fn get_copilot_where_log() -> Result<String, String> {
             if log.is_empty() {
                 log.push_str("No output from which copilot");
             }
-            return Ok(log.trim_end().to_string());
+            Ok(log.trim_end().to_string())
         }
     })
     .await
@@ -217,90 +753,31 @@ async fn get_copilot_where_log() -> Result<String, String> {
 }
 
 #[tauri::command]
-async fn install_copilot_cli() -> Result<String, String> {
-    tauri::async_runtime::spawn_blocking(move || {
-        if cfg!(target_os = "macos") {
-            if !command_available("brew", &["--version"]) {
-                return Err("Homebrew not found. Install it from https://brew.sh.".to_string());
-            }
-            let output = std::process::Command::new("brew")
-                .arg("install")
-                .arg("copilot-cli")
-                .output()
-                .map_err(|err| format!("Failed to run brew: {err}"))?;
-            if output.status.success() {
-                return Ok("Copilot CLI installed via Homebrew.".to_string());
-            }
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            return Err(stderr.trim().to_string());
-        }
-
-        if cfg!(target_os = "windows") {
-            if !command_available("winget", &["--version"]) {
-                return Err(
-                    "winget not found. Install App Installer from the Microsoft Store.".to_string(),
-                );
-            }
-            let mut cmd = std::process::Command::new("cmd");
-            cmd.args([
-                "/C",
-                "start",
-                "",
-                "/B",
-                "winget",
-                "install",
-                "GitHub.Copilot",
-            ]);
-            #[cfg(target_os = "windows")]
-            {
-                cmd.creation_flags(CREATE_NO_WINDOW);
-            }
-            cmd.stdin(Stdio::null());
-            cmd.stdout(Stdio::null());
-            cmd.stderr(Stdio::null());
-
-            cmd.spawn()
-                .map_err(|err| format!("Failed to launch winget install: {err}"))?;
-
-            return Ok("Copilot CLI install started via winget (background).".to_string());
-        }
-
-        Err("Copilot install is only supported on macOS and Windows.".to_string())
-    })
-    .await
-    .map_err(|err| format!("Failed to install copilot: {err}"))?
-}
-
-#[tauri::command]
-fn has_github_token() -> bool {
-    resolve_github_token().is_some()
+fn has_github_token(app: tauri::AppHandle) -> bool {
+    matches!(resolve_github_token(&app, None), Ok(Some(_)))
 }
 
+/// Logs out of the active account, or drops the single token stored by
+/// earlier versions when there are no accounts.
 #[tauri::command]
-fn clear_github_token() -> Result<(), String> {
+fn clear_github_token(app: tauri::AppHandle) -> Result<(), String> {
     env::remove_var("GITHUB_TOKEN");
-    let env_path = env_path()?;
-    let contents = fs::read_to_string(&env_path).unwrap_or_default();
-    let updated: String = contents
-        .lines()
-        .filter(|line| !line.trim_start().starts_with("GITHUB_TOKEN="))
-        .map(|line| format!("{line}\n"))
-        .collect();
-    if updated != contents {
-        fs::write(&env_path, updated).map_err(|err| format!("Failed to write ~/.env: {err}"))?;
+    match accounts::active_login(&app) {
+        Some(login) => accounts::remove_account(&app, &login),
+        None => credentials::active_store(&app)?.delete(credentials::GITHUB_TOKEN_KEY),
     }
-    Ok(())
 }
 
 #[derive(Serialize)]
 struct TokenStatus {
     has_token: bool,
     tail: Option<String>,
+    account: Option<String>,
 }
 
 #[tauri::command]
-fn get_token_status() -> TokenStatus {
-    if let Some(token) = resolve_github_token() {
+fn get_token_status(app: tauri::AppHandle) -> TokenStatus {
+    if let Ok(Some(token)) = resolve_github_token(&app, None) {
         let tail = token
             .chars()
             .rev()
@@ -312,19 +789,29 @@ fn get_token_status() -> TokenStatus {
         return TokenStatus {
             has_token: true,
             tail: Some(tail),
+            account: accounts::active_login(&app),
         };
     }
     TokenStatus {
         has_token: false,
         tail: None,
+        account: None,
     }
 }
 
-fn command_available(command: &str, args: &[&str]) -> bool {
-    std::process::Command::new(command)
-        .args(args)
-        .output()
-        .is_ok()
+/// Asks GitHub about the token for `account`, or the default token.
+#[tauri::command]
+async fn verify_github_token(
+    app: tauri::AppHandle,
+    account: Option<String>,
+) -> Result<github::TokenInfo, github::TokenError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let token =
+            resolve_github_token(&app, account.as_deref())?.ok_or(github::TokenError::NoToken)?;
+        github::verify_token(&app, &token)
+    })
+    .await
+    .map_err(|err| format!("Failed to verify token: {err}"))?
 }
 
 #[cfg(target_os = "windows")]
@@ -338,247 +825,55 @@ fn decode_platform_bytes(bytes: &[u8]) -> String {
     String::from_utf8_lossy(bytes).to_string()
 }
 
-fn resolve_copilot_path() -> Option<PathBuf> {
-    #[cfg(target_os = "macos")]
-    {
-        let candidates = [
-            "/opt/homebrew/bin/copilot",
-            "/usr/local/bin/copilot",
-            "/opt/local/bin/copilot",
-        ];
-        for candidate in candidates {
-            let path = PathBuf::from(candidate);
-            if path.exists() {
-                return Some(path);
-            }
-        }
-    }
-
-    #[cfg(target_os = "windows")]
-    {
-        for cmd in &["copilot", "github-copilot"] {
-            let mut command = std::process::Command::new("where");
-            command.arg(cmd);
-            command.creation_flags(CREATE_NO_WINDOW);
-            command.stdin(Stdio::null());
-            command.stdout(Stdio::piped());
-            command.stderr(Stdio::piped());
-
-            if let Ok(output) = command.output() {
-                if output.status.success() {
-                    let stdout = decode_platform_bytes(&output.stdout);
-                    if let Some(first) = stdout.lines().next() {
-                        let trimmed = first.trim();
-                        if !trimmed.is_empty() {
-                            let path = PathBuf::from(trimmed);
-                            if path.exists() {
-                                return Some(path);
-                            }
-                        }
-                    }
-                }
-            }
-        }
-    }
-
-    None
-}
-
-fn augmented_path() -> String {
-    let current = env::var("PATH").unwrap_or_default();
-    let separator = if cfg!(target_os = "windows") {
-        ";"
-    } else {
-        ":"
-    };
-    let mut extra: Vec<&str> = Vec::new();
-    if cfg!(target_os = "macos") {
-        extra.push("/opt/homebrew/bin");
-        extra.push("/usr/local/bin");
-    }
-    if extra.is_empty() {
-        return current;
-    }
-    if current.is_empty() {
-        return extra.join(separator);
-    }
-    format!("{}{}{}", extra.join(separator), separator, current)
+fn copilot_command(app: &tauri::AppHandle) -> std::process::Command {
+    let settings = settings::current(app);
+    copilot_command_for(&settings, discovery::resolve(&settings).path)
 }
 
-fn copilot_command() -> std::process::Command {
-    let command_path = resolve_copilot_path().unwrap_or_else(|| PathBuf::from("copilot"));
+/// Builds a command for the binary at `path`, or the bare `copilot` name
+/// when nothing was found.
+fn copilot_command_for(
+    settings: &settings::Settings,
+    path: Option<PathBuf>,
+) -> std::process::Command {
+    let command_path = path.unwrap_or_else(|| PathBuf::from("copilot"));
     let mut command = std::process::Command::new(command_path);
     #[cfg(target_os = "windows")]
     {
         command.creation_flags(CREATE_NO_WINDOW);
     }
-    let path = augmented_path();
+    // Run the CLI in its own process group so cancelling or timing out also
+    // stops any helpers it spawned.
+    #[cfg(unix)]
+    {
+        command.process_group(0);
+    }
+    let path = discovery::augmented_path(settings);
     if !path.is_empty() {
         command.env("PATH", path);
     }
     command
 }
 
-#[derive(Clone, Serialize)]
-struct LoginEvent {
-    status: &'static str,
-    message: String,
-}
-
-#[derive(Serialize)]
-struct DeviceLoginStart {
-    auth_url: String,
-    user_code: String,
-    expires_in: u64,
-    interval: u64,
-}
-
-#[tauri::command]
-fn start_github_login(app: tauri::AppHandle) -> Result<DeviceLoginStart, String> {
-    let client_id = "Ov23liTEmQZzOQ2bdFcm";
-    let device = request_device_code(client_id)?;
-    let auth_url = device
-        .verification_uri_complete
-        .clone()
-        .unwrap_or_else(|| device.verification_uri.clone());
-
-    std::thread::spawn(move || {
-        let result = poll_device_token(client_id, &device.device_code, device.interval)
-            .and_then(|token| store_github_token(&token).map(|_| token));
-
-        let payload = match result {
-            Ok(_) => LoginEvent {
-                status: "ok",
-                message: "GitHub token saved to ~/.env".to_string(),
-            },
-            Err(message) => LoginEvent {
-                status: "error",
-                message,
-            },
-        };
-
-        let _ = app.emit("github-login-complete", payload);
-    });
-
-    Ok(DeviceLoginStart {
-        auth_url,
-        user_code: device.user_code,
-        expires_in: device.expires_in,
-        interval: device.interval,
-    })
-}
-
-#[derive(Deserialize)]
-struct DeviceCodeResponse {
-    device_code: String,
-    user_code: String,
-    verification_uri: String,
-    verification_uri_complete: Option<String>,
-    expires_in: u64,
-    interval: u64,
-}
-
-fn request_device_code(client_id: &str) -> Result<DeviceCodeResponse, String> {
-    let client = reqwest::blocking::Client::new();
-    let response = client
-        .post("https://github.com/login/device/code")
-        .header("Accept", "application/json")
-        .form(&[("client_id", client_id), ("scope", "read:user")])
-        .send()
-        .map_err(|err| format!("Failed to request device code: {err}"))?;
-
-    response
-        .json::<DeviceCodeResponse>()
-        .map_err(|err| format!("Failed to parse device code response: {err}"))
-}
-
-fn poll_device_token(client_id: &str, device_code: &str, interval: u64) -> Result<String, String> {
-    let client = reqwest::blocking::Client::new();
-    let mut wait = interval.max(5);
-    let mut attempts = 0u32;
-    loop {
-        std::thread::sleep(Duration::from_secs(wait));
-        attempts = attempts.saturating_add(1);
-
-        let response = client
-            .post("https://github.com/login/oauth/access_token")
-            .header("Accept", "application/json")
-            .form(&[
-                ("client_id", client_id),
-                ("device_code", device_code),
-                ("grant_type", "urn:ietf:params:oauth:grant-type:device_code"),
-            ])
-            .send()
-            .map_err(|err| format!("Failed to poll token: {err}"))?;
-
-        let json: serde_json::Value = response
-            .json()
-            .map_err(|err| format!("Failed to parse token response: {err}"))?;
-
-        if let Some(token) = json.get("access_token").and_then(|value| value.as_str()) {
-            return Ok(token.to_string());
-        }
-
-        if let Some(error) = json.get("error").and_then(|value| value.as_str()) {
-            match error {
-                "authorization_pending" => continue,
-                "slow_down" => {
-                    wait = wait.saturating_add(5);
-                    continue;
-                }
-                "expired_token" => return Err("Device code expired. Please try again.".to_string()),
-                "access_denied" => return Err("Access denied. Please try again.".to_string()),
-                _ => {
-                    return Err(format!("OAuth error: {error}"));
-                }
-            }
-        }
-
-        if attempts > 120 {
-            return Err("Login timed out. Please try again.".to_string());
+/// Picks the token for `account`. Without an explicit account, a
+/// `GITHUB_TOKEN` environment variable wins over the active account.
+fn resolve_github_token(
+    app: &tauri::AppHandle,
+    account: Option<&str>,
+) -> Result<Option<String>, String> {
+    if account.is_none() {
+        if let Some(token) = env_github_token() {
+            return Ok(Some(token));
         }
     }
+    accounts::token_for(app, account)
 }
 
-fn store_github_token(token: &str) -> Result<(), String> {
-    let env_path = env_path()?;
-    let mut contents = fs::read_to_string(&env_path).unwrap_or_default();
-    contents = contents
-        .lines()
-        .filter(|line| !line.trim_start().starts_with("GITHUB_TOKEN="))
-        .map(|line| format!("{line}\n"))
-        .collect();
-
-    contents.push_str(&format!("GITHUB_TOKEN={}\n", token));
-    fs::write(&env_path, contents).map_err(|err| format!("Failed to write ~/.env: {err}"))?;
-    Ok(())
-}
-
-fn resolve_github_token() -> Option<String> {
-    if let Ok(token) = env::var("GITHUB_TOKEN") {
-        if !token.trim().is_empty() {
-            return Some(token);
-        }
-    }
-    let env_path = env_path().ok()?;
-    let contents = fs::read_to_string(env_path).ok()?;
-    for line in contents.lines() {
-        let trimmed = line.trim();
-        if let Some(value) = trimmed.strip_prefix("GITHUB_TOKEN=") {
-            if !value.trim().is_empty() {
-                return Some(value.trim().to_string());
-            }
-        }
-    }
-    None
-}
-
-fn env_path() -> Result<PathBuf, String> {
-    // Windows에서는 USERPROFILE을, 그 외에는 HOME을 사용하도록 수정합니다.
-    let home = env::var("HOME")
-        .or_else(|_| env::var("USERPROFILE"))
-        .map_err(|_| "Missing HOME or USERPROFILE environment variable".to_string())?;
-    Ok(PathBuf::from(home).join(".env"))
+/// The non-empty `GITHUB_TOKEN` environment variable, if set.
+pub(crate) fn env_github_token() -> Option<String> {
+    env::var("GITHUB_TOKEN")
+        .ok()
+        .filter(|token| !token.trim().is_empty())
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -586,34 +881,68 @@ pub fn run() {
     tauri::Builder::default()
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_opener::init())
-        .on_window_event(|_window, event| {
+        .manage(CopilotRuns::default())
+        .manage(diagnostics::ErrorLog::default())
+        .manage(models::ModelCache::default())
+        .manage(login::LoginState::default())
+        .manage(search::SearchIndex::default())
+        .setup(|app| {
+            app.manage(settings::SettingsState::load(app.handle()));
+            app.manage(history::HistoryStore::new(app.handle())?);
+            app.manage(usage::UsageLedger::new(app.handle())?);
+            app.manage(accounts::AccountRegistry::load(app.handle()));
+            app.manage(templates::TemplateStore::load(app.handle()));
+            // Left as `auto` when it cannot be settled yet; the next launch
+            // tries again.
+            let _ = settings::resolve_credential_backend(app.handle());
+            // A failed migration leaves the token in ~/.env, where the next
+            // launch will pick it up again.
+            let _ = credentials::migrate_env_token(app.handle());
+            Ok(())
+        })
+        .on_window_event(|window, event| {
             if let tauri::WindowEvent::Destroyed = event {
-                let dir = env::temp_dir().join("ghc");
-                if dir.exists() {
-                    if let Ok(entries) = fs::read_dir(dir) {
-                        for entry in entries.flatten() {
-                            let path = entry.path();
-                            if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
-                                if name.starts_with(".copilot-context-") {
-                                    let _ = fs::remove_file(path);
-                                }
-                            }
-                        }
-                    }
-                }
+                let settings = settings::current(window.app_handle());
+                context::sweep_staging(&context::temp_root(&settings.temp_dir_name));
             }
         })
         .invoke_handler(tauri::generate_handler![
             greet,
             run_copilot,
+            cancel_copilot,
+            inspect_context,
             get_copilot_version,
             get_copilot_status,
+            models::list_models,
             get_copilot_where_log,
-            install_copilot_cli,
-            start_github_login,
+            diagnostics::collect_diagnostics,
+            installer::install_copilot_cli,
+            installer::check_copilot_update,
+            installer::upgrade_copilot_cli,
+            login::start_github_login,
+            login::cancel_github_login,
             has_github_token,
             get_token_status,
-            clear_github_token
+            verify_github_token,
+            clear_github_token,
+            accounts::list_accounts,
+            accounts::switch_account,
+            history::list_conversations,
+            history::load_conversation,
+            history::save_conversation,
+            history::delete_conversation,
+            search::search_history,
+            conversation::fork_conversation,
+            export::export_conversation,
+            export::import_conversation,
+            templates::list_templates,
+            templates::create_template,
+            templates::update_template,
+            templates::delete_template,
+            templates::render_template,
+            usage::get_usage_summary,
+            settings::get_settings,
+            settings::update_settings
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -0,0 +1,371 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::{Emitter, Manager};
+
+use crate::{accounts, github, history};
+
+/// How often the poller wakes to check for cancellation and report progress.
+const PROGRESS_TICK: Duration = Duration::from_secs(1);
+/// Longest wait between retries after network errors.
+const MAX_BACKOFF_SECS: u64 = 60;
+
+struct ActiveLogin {
+    id: String,
+    cancelled: Arc<AtomicBool>,
+}
+
+/// The device-flow login currently polling for a token, if any.
+#[derive(Default)]
+pub(crate) struct LoginState(Mutex<Option<ActiveLogin>>);
+
+#[derive(Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum LoginError {
+    /// The device code expired before the user approved it.
+    Expired,
+    /// The user declined the authorization request.
+    Denied,
+    Cancelled,
+    /// GitHub stayed unreachable or kept failing until the code expired.
+    Network {
+        message: String,
+    },
+    /// GitHub answered in a way the device flow does not allow for.
+    Protocol {
+        message: String,
+    },
+    /// The token was issued but could not be verified or saved.
+    Failed {
+        message: String,
+    },
+}
+
+impl fmt::Display for LoginError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoginError::Expired => write!(f, "Device code expired. Please try again."),
+            LoginError::Denied => write!(f, "Access denied. Please try again."),
+            LoginError::Cancelled => write!(f, "Login cancelled."),
+            LoginError::Network { message }
+            | LoginError::Protocol { message }
+            | LoginError::Failed { message } => write!(f, "{message}"),
+        }
+    }
+}
+
+impl From<String> for LoginError {
+    fn from(message: String) -> Self {
+        LoginError::Failed { message }
+    }
+}
+
+#[derive(Clone, Serialize)]
+struct LoginEvent {
+    login_id: String,
+    /// `ok`, `error` or `cancelled`.
+    status: &'static str,
+    message: String,
+    error: Option<LoginError>,
+}
+
+#[derive(Clone, Serialize)]
+struct LoginProgressEvent {
+    login_id: String,
+    /// Seconds left before the device code expires.
+    remaining_secs: u64,
+    /// Current polling interval, raised by `slow_down` responses.
+    interval_secs: u64,
+    attempts: u32,
+}
+
+#[derive(Serialize)]
+pub(crate) struct DeviceLoginStart {
+    login_id: String,
+    auth_url: String,
+    user_code: String,
+    expires_in: u64,
+    interval: u64,
+}
+
+/// Starts the device flow. A login that is already polling is cancelled and
+/// replaced, so only one poller ever saves a token.
+#[tauri::command]
+pub(crate) fn start_github_login(app: tauri::AppHandle) -> Result<DeviceLoginStart, String> {
+    let config = github::GitHubConfig::resolve(&app);
+    let device = request_device_code(&config)?;
+    let auth_url = device
+        .verification_uri_complete
+        .clone()
+        .unwrap_or_else(|| device.verification_uri.clone());
+
+    let login_id = history::new_id();
+    let cancelled = Arc::new(AtomicBool::new(false));
+    {
+        let state = app.state::<LoginState>();
+        let mut active = state
+            .0
+            .lock()
+            .map_err(|_| "Login state is poisoned".to_string())?;
+        if let Some(previous) = active.replace(ActiveLogin {
+            id: login_id.clone(),
+            cancelled: cancelled.clone(),
+        }) {
+            previous.cancelled.store(true, Ordering::SeqCst);
+        }
+    }
+
+    let poll = Poll {
+        app: app.clone(),
+        login_id: login_id.clone(),
+        cancelled,
+        started: Instant::now(),
+        expires_in: device.expires_in,
+    };
+    std::thread::spawn(move || {
+        let result = poll_device_token(&poll, &config, &device.device_code, device.interval)
+            .and_then(|token| {
+                let info = github::verify_token(&app, &token).map_err(|err| err.to_string())?;
+                // Cancelling or replacing a login sets its flag under this
+                // lock, so a login stopped while the token was verified
+                // drops it instead of saving it.
+                let state = app.state::<LoginState>();
+                let _active = state
+                    .0
+                    .lock()
+                    .map_err(|_| "Login state is poisoned".to_string())?;
+                if poll.is_cancelled() {
+                    return Err(LoginError::Cancelled);
+                }
+                accounts::add_account(&app, &info.login, &token)
+                    .map(|store| (info.login, store))
+                    .map_err(LoginError::from)
+            });
+
+        let payload = match result {
+            Ok((login, store)) => LoginEvent {
+                login_id: poll.login_id.clone(),
+                status: "ok",
+                message: format!("Logged in as {login}; token saved to {store}"),
+                error: None,
+            },
+            Err(error) => {
+                if !matches!(error, LoginError::Cancelled) {
+                    crate::diagnostics::record_error(&app, "login", &error.to_string());
+                }
+                LoginEvent {
+                    login_id: poll.login_id.clone(),
+                    status: match error {
+                        LoginError::Cancelled => "cancelled",
+                        _ => "error",
+                    },
+                    message: error.to_string(),
+                    error: Some(error),
+                }
+            }
+        };
+
+        if let Ok(mut active) = app.state::<LoginState>().0.lock() {
+            if active
+                .as_ref()
+                .is_some_and(|login| login.id == poll.login_id)
+            {
+                *active = None;
+            }
+        }
+        let _ = app.emit("github-login-complete", payload);
+    });
+
+    Ok(DeviceLoginStart {
+        login_id,
+        auth_url,
+        user_code: device.user_code,
+        expires_in: device.expires_in,
+        interval: device.interval,
+    })
+}
+
+/// Stops the login that is polling for a token. Does nothing when no login
+/// is in progress.
+#[tauri::command]
+pub(crate) fn cancel_github_login(state: tauri::State<'_, LoginState>) -> Result<(), String> {
+    let active = state
+        .0
+        .lock()
+        .map_err(|_| "Login state is poisoned".to_string())?;
+    if let Some(login) = active.as_ref() {
+        login.cancelled.store(true, Ordering::SeqCst);
+    }
+    Ok(())
+}
+
+#[derive(Deserialize)]
+struct DeviceCodeResponse {
+    device_code: String,
+    user_code: String,
+    verification_uri: String,
+    verification_uri_complete: Option<String>,
+    expires_in: u64,
+    interval: u64,
+}
+
+fn request_device_code(config: &github::GitHubConfig) -> Result<DeviceCodeResponse, String> {
+    let client = reqwest::blocking::Client::new();
+    let scope = config.scopes.join(" ");
+    let response = client
+        .post(config.device_code_url())
+        .header("Accept", "application/json")
+        .form(&[("client_id", config.client_id.as_str()), ("scope", &scope)])
+        .send()
+        .map_err(|err| format!("Failed to request device code: {err}"))?;
+
+    response
+        .json::<DeviceCodeResponse>()
+        .map_err(|err| format!("Failed to parse device code response: {err}"))
+}
+
+/// One polling thread's view of its login.
+struct Poll {
+    app: tauri::AppHandle,
+    login_id: String,
+    cancelled: Arc<AtomicBool>,
+    started: Instant,
+    expires_in: u64,
+}
+
+impl Poll {
+    fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    fn remaining_secs(&self) -> u64 {
+        self.expires_in
+            .saturating_sub(self.started.elapsed().as_secs())
+    }
+
+    /// Sleeps for `wait` seconds, reporting progress every tick. Returns
+    /// `false` as soon as the login is cancelled.
+    fn wait(&self, wait: u64, attempts: u32) -> bool {
+        let until = Instant::now() + Duration::from_secs(wait);
+        loop {
+            if self.is_cancelled() {
+                return false;
+            }
+            let _ = self.app.emit(
+                "github-login-progress",
+                LoginProgressEvent {
+                    login_id: self.login_id.clone(),
+                    remaining_secs: self.remaining_secs(),
+                    interval_secs: wait,
+                    attempts,
+                },
+            );
+            let now = Instant::now();
+            if now >= until {
+                return true;
+            }
+            std::thread::sleep(PROGRESS_TICK.min(until - now));
+        }
+    }
+}
+
+/// Delay before retrying after `failures` consecutive network errors or 5xx
+/// responses: the poll interval, doubled per failure, capped.
+fn backoff_secs(wait: u64, failures: u32) -> u64 {
+    wait.saturating_mul(1 << failures.min(6))
+        .min(MAX_BACKOFF_SECS)
+}
+
+/// Polls until the user approves the device code, it expires, or the login
+/// is cancelled. Transient failures are retried with backoff until the code
+/// expires.
+fn poll_device_token(
+    poll: &Poll,
+    config: &github::GitHubConfig,
+    device_code: &str,
+    interval: u64,
+) -> Result<String, LoginError> {
+    let client = reqwest::blocking::Client::new();
+    let mut wait = interval.max(5);
+    let mut attempts = 0u32;
+    let mut failures = 0u32;
+    let mut last_failure: Option<String> = None;
+    loop {
+        let delay = if failures == 0 {
+            wait
+        } else {
+            backoff_secs(wait, failures)
+        };
+        if !poll.wait(delay.min(poll.remaining_secs()), attempts) {
+            return Err(LoginError::Cancelled);
+        }
+        if poll.remaining_secs() == 0 {
+            // Report the outage rather than the expiry it caused.
+            return Err(match last_failure {
+                Some(message) => LoginError::Network { message },
+                None => LoginError::Expired,
+            });
+        }
+        attempts = attempts.saturating_add(1);
+
+        let response = match client
+            .post(config.access_token_url())
+            .header("Accept", "application/json")
+            .form(&[
+                ("client_id", config.client_id.as_str()),
+                ("device_code", device_code),
+                ("grant_type", "urn:ietf:params:oauth:grant-type:device_code"),
+            ])
+            .send()
+        {
+            Ok(response) if response.status().is_server_error() => {
+                failures = failures.saturating_add(1);
+                last_failure = Some(format!("GitHub returned {}", response.status()));
+                continue;
+            }
+            Ok(response) => response,
+            Err(err) => {
+                failures = failures.saturating_add(1);
+                last_failure = Some(format!("Failed to poll token: {err}"));
+                continue;
+            }
+        };
+        failures = 0;
+        last_failure = None;
+
+        let json: serde_json::Value = response.json().map_err(|err| LoginError::Protocol {
+            message: format!("Failed to parse token response: {err}"),
+        })?;
+
+        if let Some(token) = json.get("access_token").and_then(|value| value.as_str()) {
+            return Ok(token.to_string());
+        }
+
+        let Some(error) = json.get("error").and_then(|value| value.as_str()) else {
+            return Err(LoginError::Protocol {
+                message: "Token response had neither a token nor an error".to_string(),
+            });
+        };
+        match error {
+            "authorization_pending" => continue,
+            "slow_down" => {
+                // GitHub may send the new interval; otherwise add 5 seconds
+                // as RFC 8628 requires.
+                wait = json
+                    .get("interval")
+                    .and_then(|value| value.as_u64())
+                    .unwrap_or_else(|| wait.saturating_add(5));
+                continue;
+            }
+            "expired_token" => return Err(LoginError::Expired),
+            "access_denied" => return Err(LoginError::Denied),
+            _ => {
+                return Err(LoginError::Protocol {
+                    message: format!("OAuth error: {error}"),
+                });
+            }
+        }
+    }
+}
@@ -0,0 +1,171 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::sync::Mutex;
+use tauri::Manager;
+
+use crate::settings;
+
+/// Catalog shipped with the app, used when the CLI does not list its models.
+const BUNDLED_CATALOG: &str = include_str!("models.json");
+/// A `models.json` in the app config dir replaces the bundled catalog.
+const CATALOG_FILE: &str = "models.json";
+
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) struct ModelInfo {
+    pub(crate) id: String,
+    /// Premium requests charged per prompt. `None` when the model is not in
+    /// the catalog.
+    #[serde(default)]
+    pub(crate) multiplier: Option<f64>,
+    /// Preselected in the model picker unless the `default_model` setting
+    /// names another offered model.
+    #[serde(default)]
+    pub(crate) default: bool,
+}
+
+#[derive(Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum ModelSource {
+    /// The model choices printed by `copilot --help`.
+    Cli,
+    /// The user's `models.json` in the app config dir.
+    UserCatalog,
+    BundledCatalog,
+}
+
+#[derive(Clone, Serialize)]
+pub(crate) struct ModelList {
+    pub(crate) source: ModelSource,
+    pub(crate) models: Vec<ModelInfo>,
+}
+
+impl ModelList {
+    pub(crate) fn contains(&self, id: &str) -> bool {
+        self.models.iter().any(|model| model.id == id)
+    }
+}
+
+/// Models discovered on first use; cleared by `list_models(refresh: true)`.
+#[derive(Default)]
+pub(crate) struct ModelCache(Mutex<Option<ModelList>>);
+
+/// The user catalog if there is a readable one, otherwise the bundled one.
+fn load_catalog(app: &tauri::AppHandle) -> (ModelSource, Vec<ModelInfo>) {
+    let user = app
+        .path()
+        .app_config_dir()
+        .ok()
+        .and_then(|dir| fs::read_to_string(dir.join(CATALOG_FILE)).ok())
+        .and_then(|contents| serde_json::from_str::<Vec<ModelInfo>>(&contents).ok())
+        .filter(|models| !models.is_empty());
+    match user {
+        Some(models) => (ModelSource::UserCatalog, models),
+        None => (
+            ModelSource::BundledCatalog,
+            serde_json::from_str(BUNDLED_CATALOG).unwrap_or_default(),
+        ),
+    }
+}
+
+/// Extracts the quoted choices of the `--model` option from `copilot --help`,
+/// e.g. `--model <model>  Set the AI model (choices: "gpt-5", "claude-sonnet-4")`.
+fn parse_help_models(help: &str) -> Vec<String> {
+    let Some(start) = help.find("--model") else {
+        return Vec::new();
+    };
+    let section = &help[start + "--model".len()..];
+    // The option's description ends where the next option starts.
+    let end = section
+        .match_indices("\n")
+        .map(|(idx, _)| idx)
+        .find(|idx| section[idx + 1..].trim_start().starts_with('-'))
+        .unwrap_or(section.len());
+    let Some(choices) = section[..end].find("choices:") else {
+        return Vec::new();
+    };
+    section[..end][choices..]
+        .split('"')
+        .skip(1)
+        .step_by(2)
+        .map(|choice| choice.trim().to_string())
+        .filter(|choice| !choice.is_empty())
+        .collect()
+}
+
+fn discover_cli_models(app: &tauri::AppHandle) -> Option<Vec<String>> {
+    let mut command = crate::copilot_command(app);
+    command.arg("--help");
+    let output = crate::probe_output(command, settings::current(app).probe_timeout_secs).ok()?;
+    let help = String::from_utf8_lossy(&output.stdout);
+    Some(parse_help_models(&help)).filter(|models| !models.is_empty())
+}
+
+/// Lists models from the CLI when it names them, annotated with catalog
+/// multipliers; otherwise the catalog itself.
+fn discover(app: &tauri::AppHandle) -> ModelList {
+    let (source, catalog) = load_catalog(app);
+    let Some(ids) = discover_cli_models(app) else {
+        return ModelList {
+            source,
+            models: catalog,
+        };
+    };
+    let models = ids
+        .into_iter()
+        .map(|id| {
+            let known = catalog.iter().find(|model| model.id == id);
+            ModelInfo {
+                multiplier: known.and_then(|model| model.multiplier),
+                default: known.is_some_and(|model| model.default),
+                id,
+            }
+        })
+        .collect();
+    ModelList {
+        source: ModelSource::Cli,
+        models,
+    }
+}
+
+/// Forgets the discovered models, e.g. after the Copilot binary changed.
+pub(crate) fn clear_cache(app: &tauri::AppHandle) {
+    if let Ok(mut cached) = app.state::<ModelCache>().0.lock() {
+        *cached = None;
+    }
+}
+
+/// Returns the cached model list, discovering it on first use.
+pub(crate) fn available(app: &tauri::AppHandle) -> ModelList {
+    let cache = app.state::<ModelCache>();
+    if let Some(list) = cache.0.lock().ok().and_then(|cached| cached.clone()) {
+        return list;
+    }
+    let list = discover(app);
+    if let Ok(mut cached) = cache.0.lock() {
+        *cached = Some(list.clone());
+    }
+    list
+}
+
+#[tauri::command]
+pub(crate) async fn list_models(
+    app: tauri::AppHandle,
+    refresh: Option<bool>,
+) -> Result<ModelList, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        if refresh.unwrap_or(false) {
+            clear_cache(&app);
+        }
+        let mut list = available(&app);
+        // The configured default wins over the catalog's when it is offered.
+        let default_model = settings::current(&app).default_model;
+        if list.contains(&default_model) {
+            for model in &mut list.models {
+                model.default = model.id == default_model;
+            }
+        }
+        list
+    })
+    .await
+    .map_err(|err| format!("Failed to list models: {err}"))
+}
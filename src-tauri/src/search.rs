@@ -0,0 +1,358 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use tauri::Manager;
+
+use crate::history::{title_from_prompt, HistoryStore};
+
+/// BM25 tuning constants.
+const K1: f64 = 1.2;
+const B: f64 = 0.75;
+/// Prompt matches count this many times more than output matches.
+const PROMPT_WEIGHT: u32 = 2;
+/// Roughly how many bytes of context a snippet keeps around the first match.
+const SNIPPET_BEFORE: usize = 60;
+const SNIPPET_LENGTH: usize = 200;
+const DEFAULT_LIMIT: usize = 50;
+
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub(crate) struct SearchFilters {
+    pub(crate) model: Option<String>,
+    /// Inclusive bounds on a turn's start time, in Unix milliseconds.
+    pub(crate) from: Option<u64>,
+    pub(crate) to: Option<u64>,
+    /// Case-insensitive substring of an attached context file name.
+    pub(crate) context_file: Option<String>,
+    pub(crate) limit: Option<usize>,
+}
+
+#[derive(Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum MatchField {
+    Prompt,
+    Output,
+}
+
+#[derive(Serialize)]
+pub(crate) struct SearchHit {
+    pub(crate) conversation_id: String,
+    pub(crate) turn_id: String,
+    pub(crate) title: String,
+    pub(crate) model: String,
+    pub(crate) started_at: u64,
+    pub(crate) score: f64,
+    pub(crate) field: MatchField,
+    pub(crate) snippet: String,
+    /// `[start, end)` ranges into `snippet`, in UTF-16 code units so they can
+    /// be used with `String.prototype.slice` directly.
+    pub(crate) highlights: Vec<[usize; 2]>,
+}
+
+struct IndexedTurn {
+    conversation_id: String,
+    turn_id: String,
+    title: String,
+    model: String,
+    started_at: u64,
+    context_files: Vec<String>,
+    prompt: String,
+    output: String,
+    length: usize,
+}
+
+struct Posting {
+    turn: usize,
+    prompt_tf: u32,
+    output_tf: u32,
+}
+
+/// Inverted index over the prompts and outputs in the [`HistoryStore`].
+struct Index {
+    revision: u64,
+    turns: Vec<IndexedTurn>,
+    postings: HashMap<String, Vec<Posting>>,
+    average_length: f64,
+}
+
+/// Lazily built search index, rebuilt whenever the history store changes.
+#[derive(Default)]
+pub(crate) struct SearchIndex(Mutex<Option<Index>>);
+
+impl Index {
+    fn build(store: &HistoryStore) -> Self {
+        let revision = store.revision();
+        let mut index = Index {
+            revision,
+            turns: Vec::new(),
+            postings: HashMap::new(),
+            average_length: 0.0,
+        };
+        let mut total_length = 0;
+        for conversation in store.load_all() {
+            let title = conversation
+                .turns
+                .first()
+                .map(|turn| title_from_prompt(&turn.prompt))
+                .unwrap_or_default();
+            for turn in conversation.turns {
+                let slot = index.turns.len();
+                let mut counts: HashMap<String, (u32, u32)> = HashMap::new();
+                let prompt_tokens = tokenize(&turn.prompt);
+                let output_tokens = tokenize(&turn.output);
+                for token in &prompt_tokens {
+                    counts.entry(token.term.clone()).or_default().0 += 1;
+                }
+                for token in &output_tokens {
+                    counts.entry(token.term.clone()).or_default().1 += 1;
+                }
+                for (term, (prompt_tf, output_tf)) in counts {
+                    index.postings.entry(term).or_default().push(Posting {
+                        turn: slot,
+                        prompt_tf,
+                        output_tf,
+                    });
+                }
+                let length = prompt_tokens.len() + output_tokens.len();
+                total_length += length;
+                index.turns.push(IndexedTurn {
+                    conversation_id: turn.conversation_id,
+                    turn_id: turn.id,
+                    title: title.clone(),
+                    model: turn.model,
+                    started_at: turn.started_at,
+                    context_files: turn.context_files,
+                    prompt: turn.prompt,
+                    output: turn.output,
+                    length,
+                });
+            }
+        }
+        if !index.turns.is_empty() {
+            index.average_length = total_length as f64 / index.turns.len() as f64;
+        }
+        index
+    }
+
+    fn search(&self, query: &str, filters: &SearchFilters) -> Vec<SearchHit> {
+        let terms: HashSet<String> = tokenize(query)
+            .into_iter()
+            .map(|token| token.term)
+            .collect();
+        let mut scores: HashMap<usize, f64> = HashMap::new();
+        if terms.is_empty() {
+            // No query: list every turn that passes the filters.
+            scores.extend((0..self.turns.len()).map(|slot| (slot, 0.0)));
+        }
+        let total = self.turns.len() as f64;
+        for term in &terms {
+            let Some(postings) = self.postings.get(term) else {
+                continue;
+            };
+            let df = postings.len() as f64;
+            let idf = (1.0 + (total - df + 0.5) / (df + 0.5)).ln();
+            for posting in postings {
+                let tf = f64::from(posting.prompt_tf * PROMPT_WEIGHT + posting.output_tf);
+                let length = self.turns[posting.turn].length as f64;
+                let norm = 1.0 - B + B * length / self.average_length.max(1.0);
+                *scores.entry(posting.turn).or_default() +=
+                    idf * tf * (K1 + 1.0) / (tf + K1 * norm);
+            }
+        }
+
+        let mut hits: Vec<SearchHit> = scores
+            .into_iter()
+            .filter(|(slot, _)| matches_filters(&self.turns[*slot], filters))
+            .map(|(slot, score)| {
+                let turn = &self.turns[slot];
+                let prompt_match = terms.is_empty()
+                    || tokenize(&turn.prompt)
+                        .iter()
+                        .any(|token| terms.contains(&token.term));
+                let (field, text) = if prompt_match {
+                    (MatchField::Prompt, &turn.prompt)
+                } else {
+                    (MatchField::Output, &turn.output)
+                };
+                let (snippet, highlights) = snippet(text, &terms);
+                SearchHit {
+                    conversation_id: turn.conversation_id.clone(),
+                    turn_id: turn.turn_id.clone(),
+                    title: turn.title.clone(),
+                    model: turn.model.clone(),
+                    started_at: turn.started_at,
+                    score,
+                    field,
+                    snippet,
+                    highlights,
+                }
+            })
+            .collect();
+        hits.sort_by(|a, b| {
+            b.score
+                .total_cmp(&a.score)
+                .then(b.started_at.cmp(&a.started_at))
+        });
+        hits.truncate(filters.limit.unwrap_or(DEFAULT_LIMIT));
+        hits
+    }
+}
+
+fn matches_filters(turn: &IndexedTurn, filters: &SearchFilters) -> bool {
+    if let Some(model) = filters.model.as_deref() {
+        if !model.is_empty() && turn.model != model {
+            return false;
+        }
+    }
+    if filters.from.is_some_and(|from| turn.started_at < from) {
+        return false;
+    }
+    if filters.to.is_some_and(|to| turn.started_at > to) {
+        return false;
+    }
+    if let Some(file) = filters.context_file.as_deref() {
+        let needle = file.to_lowercase();
+        if !needle.is_empty()
+            && !turn
+                .context_files
+                .iter()
+                .any(|name| name.to_lowercase().contains(&needle))
+        {
+            return false;
+        }
+    }
+    true
+}
+
+struct Token {
+    start: usize,
+    end: usize,
+    term: String,
+}
+
+/// Splits `text` into lowercase alphanumeric words with their byte spans.
+fn tokenize(text: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+    for (idx, ch) in text.char_indices() {
+        if ch.is_alphanumeric() || ch == '_' {
+            start.get_or_insert(idx);
+        } else if let Some(begin) = start.take() {
+            tokens.push(Token {
+                start: begin,
+                end: idx,
+                term: text[begin..idx].to_lowercase(),
+            });
+        }
+    }
+    if let Some(begin) = start {
+        tokens.push(Token {
+            start: begin,
+            end: text.len(),
+            term: text[begin..].to_lowercase(),
+        });
+    }
+    tokens
+}
+
+/// Cuts a window of `text` around the first match of `terms` and returns it
+/// with the UTF-16 ranges of every match inside it.
+fn snippet(text: &str, terms: &HashSet<String>) -> (String, Vec<[usize; 2]>) {
+    let tokens = tokenize(text);
+    let first = tokens
+        .iter()
+        .find(|token| terms.contains(&token.term))
+        .map(|token| token.start)
+        .unwrap_or(0);
+    let mut start = first.saturating_sub(SNIPPET_BEFORE);
+    while !text.is_char_boundary(start) {
+        start -= 1;
+    }
+    let mut end = (start + SNIPPET_LENGTH).min(text.len());
+    while !text.is_char_boundary(end) {
+        end += 1;
+    }
+
+    let prefix = if start > 0 { "…" } else { "" };
+    let suffix = if end < text.len() { "…" } else { "" };
+    let snippet = format!("{prefix}{}{suffix}", &text[start..end]);
+    let utf16 =
+        |byte: usize| prefix.encode_utf16().count() + text[start..byte].encode_utf16().count();
+    let highlights = tokens
+        .iter()
+        .filter(|token| token.start >= start && token.end <= end && terms.contains(&token.term))
+        .map(|token| [utf16(token.start), utf16(token.end)])
+        .collect();
+    (snippet, highlights)
+}
+
+#[tauri::command]
+pub(crate) async fn search_history(
+    app: tauri::AppHandle,
+    query: String,
+    filters: Option<SearchFilters>,
+) -> Result<Vec<SearchHit>, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let store = app.state::<HistoryStore>();
+        let state = app.state::<SearchIndex>();
+        let mut index = state
+            .0
+            .lock()
+            .map_err(|_| "Search index is poisoned".to_string())?;
+        if index
+            .as_ref()
+            .is_none_or(|index| index.revision != store.revision())
+        {
+            *index = Some(Index::build(&store));
+        }
+        let filters = filters.unwrap_or_default();
+        Ok(index
+            .as_ref()
+            .map(|index| index.search(&query, &filters))
+            .unwrap_or_default())
+    })
+    .await
+    .map_err(|err| format!("Failed to search history: {err}"))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn terms(words: &[&str]) -> HashSet<String> {
+        words.iter().map(|word| word.to_string()).collect()
+    }
+
+    /// The highlighted parts of `snippet`, sliced the way the frontend does.
+    fn highlighted(snippet: &str, highlights: &[[usize; 2]]) -> Vec<String> {
+        let utf16: Vec<u16> = snippet.encode_utf16().collect();
+        highlights
+            .iter()
+            .map(|[start, end]| String::from_utf16(&utf16[*start..*end]).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn snippet_highlights_are_utf16_ranges() {
+        let text = "😀 Café rocks, café again";
+        let (snippet, highlights) = snippet(text, &terms(&["café"]));
+        assert_eq!(snippet, text);
+        assert_eq!(highlighted(&snippet, &highlights), vec!["Café", "café"]);
+    }
+
+    #[test]
+    fn long_text_is_cut_on_char_boundaries_around_the_match() {
+        let text = format!("{}needle{}", "é ".repeat(100), " tail".repeat(100));
+        let (snippet, highlights) = snippet(&text, &terms(&["needle"]));
+        assert!(snippet.starts_with('…'));
+        assert!(snippet.ends_with('…'));
+        assert_eq!(highlighted(&snippet, &highlights), vec!["needle"]);
+    }
+
+    #[test]
+    fn snippet_without_a_match_starts_at_the_beginning() {
+        let (snippet, highlights) = snippet("short text", &terms(&["missing"]));
+        assert_eq!(snippet, "short text");
+        assert!(highlights.is_empty());
+    }
+}
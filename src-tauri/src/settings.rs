@@ -0,0 +1,676 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use tauri::{Emitter, Manager};
+
+use crate::context::{ContextPolicy, OversizeAction};
+use crate::credentials::{self, CredentialBackend};
+use crate::usage::UsageBudget;
+use crate::{accounts, models};
+
+const SETTINGS_FILE: &str = "settings.json";
+/// Bumped whenever a stored field is renamed or reinterpreted; [`migrate`]
+/// brings older files up to date.
+const SCHEMA_VERSION: u32 = 1;
+
+/// User-tunable options persisted as JSON in the app config dir.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct Settings {
+    /// Layout version of the stored file. Files written before versioning
+    /// count as `0`.
+    pub(crate) schema_version: u32,
+    /// Hard limit for a whole `run_copilot` call, in seconds. `0` disables it.
+    pub(crate) copilot_timeout_secs: u64,
+    /// Kill a streaming run after this many seconds without new output.
+    /// `0` disables it.
+    pub(crate) copilot_idle_timeout_secs: u64,
+    /// Copilot CLI binary to run. Takes precedence over `GHC_COPILOT_BIN`
+    /// and the PATH search.
+    pub(crate) copilot_path: Option<String>,
+    /// Homebrew install prefixes searched for `copilot` on macOS.
+    pub(crate) homebrew_prefixes: Vec<String>,
+    /// Limit for short probes such as `copilot --version`.
+    pub(crate) probe_timeout_secs: u64,
+    /// Model preselected in the picker and used when a run names none.
+    pub(crate) default_model: String,
+    /// Most earlier turns replayed into a follow-up prompt. `0` makes every
+    /// run stand alone.
+    pub(crate) conversation_max_turns: usize,
+    /// Character budget for the replayed turns plus the new prompt.
+    pub(crate) conversation_max_chars: usize,
+    /// Directory under the system temp dir that holds staged context.
+    pub(crate) temp_dir_name: String,
+    /// GitHub host to log in to, e.g. a GitHub Enterprise Server host name.
+    /// `GHC_GITHUB_HOST` overrides it.
+    pub(crate) github_host: String,
+    /// Base URL of the GitHub REST API, derived from `github_host` when
+    /// unset. `GHC_GITHUB_API_BASE` overrides it.
+    pub(crate) github_api_base: Option<String>,
+    /// OAuth app used for the device flow. `GHC_OAUTH_CLIENT_ID` overrides it.
+    pub(crate) oauth_client_id: String,
+    /// Scopes requested during login. `GHC_OAUTH_SCOPES` overrides them.
+    pub(crate) oauth_scopes: Vec<String>,
+    /// Where the GitHub token is stored.
+    pub(crate) credential_backend: CredentialBackend,
+    /// `.env` file older versions kept the token in; `~/.env` when unset.
+    pub(crate) legacy_env_file: Option<String>,
+    /// Size and content rules for attached context files.
+    pub(crate) context_policy: ContextPolicy,
+    /// Monthly premium-request limits. Both are off by default.
+    pub(crate) usage_budget: UsageBudget,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            schema_version: SCHEMA_VERSION,
+            copilot_timeout_secs: 600,
+            copilot_idle_timeout_secs: 120,
+            copilot_path: None,
+            homebrew_prefixes: vec!["/opt/homebrew".to_string(), "/usr/local".to_string()],
+            probe_timeout_secs: 15,
+            default_model: "gpt-5-mini".to_string(),
+            conversation_max_turns: 6,
+            conversation_max_chars: 24_000,
+            temp_dir_name: "ghc".to_string(),
+            github_host: "github.com".to_string(),
+            github_api_base: None,
+            oauth_client_id: "Ov23liTEmQZzOQ2bdFcm".to_string(),
+            oauth_scopes: vec!["read:user".to_string()],
+            credential_backend: CredentialBackend::Auto,
+            legacy_env_file: None,
+            context_policy: ContextPolicy::default(),
+            usage_budget: UsageBudget::default(),
+        }
+    }
+}
+
+/// A setting that failed validation.
+#[derive(Serialize)]
+pub(crate) struct FieldError {
+    /// Field name as stored, e.g. `context_policy.max_file_bytes`.
+    pub(crate) field: String,
+    pub(crate) message: String,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub(crate) enum SettingsError {
+    /// Nothing was saved; every offending field is listed.
+    Invalid {
+        errors: Vec<FieldError>,
+    },
+    Failed {
+        message: String,
+    },
+}
+
+impl fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SettingsError::Invalid { errors } => {
+                let fields: Vec<&str> = errors.iter().map(|error| error.field.as_str()).collect();
+                write!(f, "Invalid settings: {}", fields.join(", "))
+            }
+            SettingsError::Failed { message } => write!(f, "{message}"),
+        }
+    }
+}
+
+impl From<String> for SettingsError {
+    fn from(message: String) -> Self {
+        SettingsError::Failed { message }
+    }
+}
+
+/// Sent as `settings-changed` after settings are saved.
+#[derive(Clone, Serialize)]
+struct SettingsChanged {
+    settings: Settings,
+    /// Top-level fields whose value changed.
+    changed: Vec<String>,
+}
+
+pub(crate) struct SettingsState {
+    settings: Mutex<Settings>,
+    /// Set when the stored file could not be read as is. Startup then keeps
+    /// its repairs in memory and leaves the file alone until the user saves.
+    damaged: AtomicBool,
+}
+
+impl SettingsState {
+    /// Loads the stored settings, migrating and rewriting files from older
+    /// schema versions. A field that fails to parse or validate falls back to
+    /// its default on its own, and a file that needed any such repair is not
+    /// written back.
+    pub(crate) fn load(app: &tauri::AppHandle) -> Self {
+        let path = settings_path(app).ok();
+        let contents = match path.as_ref().map(fs::read_to_string) {
+            Some(Ok(contents)) => contents,
+            Some(Err(err)) if err.kind() != io::ErrorKind::NotFound => {
+                crate::diagnostics::record_error(
+                    app,
+                    "settings",
+                    &format!("Failed to read {SETTINGS_FILE}: {err}; using the defaults"),
+                );
+                return SettingsState::new(Settings::default(), true);
+            }
+            _ => return SettingsState::new(Settings::default(), false),
+        };
+        let mut value = match serde_json::from_str::<serde_json::Value>(&contents) {
+            Ok(value) => value,
+            Err(err) => {
+                crate::diagnostics::record_error(
+                    app,
+                    "settings",
+                    &format!("Failed to parse {SETTINGS_FILE}: {err}; using the defaults"),
+                );
+                return SettingsState::new(Settings::default(), true);
+            }
+        };
+        let migrated = migrate(&mut value);
+        let (settings, mut errors) = parse_fields(value);
+        let (settings, invalid) = reset_invalid(settings);
+        errors.extend(invalid);
+        for error in &errors {
+            crate::diagnostics::record_error(
+                app,
+                "settings",
+                &format!(
+                    "{SETTINGS_FILE}: {}: {}; using the default",
+                    error.field, error.message
+                ),
+            );
+        }
+        let damaged = !errors.is_empty();
+        if migrated && !damaged {
+            if let Some(path) = path {
+                let _ = write_settings(&path, &settings);
+            }
+        }
+        SettingsState::new(settings, damaged)
+    }
+
+    fn new(settings: Settings, damaged: bool) -> Self {
+        SettingsState {
+            settings: Mutex::new(settings),
+            damaged: AtomicBool::new(damaged),
+        }
+    }
+}
+
+/// Parses a stored settings object one top-level field at a time, so a field
+/// of the wrong type falls back to its default without taking the others
+/// with it. Unknown fields are ignored.
+fn parse_fields(value: serde_json::Value) -> (Settings, Vec<FieldError>) {
+    let serde_json::Value::Object(stored) = value else {
+        return (
+            Settings::default(),
+            vec![FieldError {
+                field: SETTINGS_FILE.to_string(),
+                message: "Must be a JSON object".to_string(),
+            }],
+        );
+    };
+    let Ok(serde_json::Value::Object(mut fields)) = serde_json::to_value(Settings::default())
+    else {
+        return (Settings::default(), Vec::new());
+    };
+    let mut errors = Vec::new();
+    for (field, value) in stored {
+        if !fields.contains_key(&field) {
+            continue;
+        }
+        let mut candidate = fields.clone();
+        candidate.insert(field.clone(), value);
+        match serde_json::from_value::<Settings>(serde_json::Value::Object(candidate.clone())) {
+            Ok(_) => fields = candidate,
+            Err(err) => errors.push(FieldError {
+                field,
+                message: err.to_string(),
+            }),
+        }
+    }
+    let settings = serde_json::from_value(serde_json::Value::Object(fields)).unwrap_or_default();
+    (settings, errors)
+}
+
+/// Upgrades a stored settings object to [`SCHEMA_VERSION`] in place and
+/// returns whether anything changed. Files from a newer version are left
+/// alone; fields this build does not know are ignored when parsing.
+fn migrate(value: &mut serde_json::Value) -> bool {
+    let Some(object) = value.as_object_mut() else {
+        return false;
+    };
+    let version = object
+        .get("schema_version")
+        .and_then(|version| version.as_u64())
+        .unwrap_or(0);
+    if version >= u64::from(SCHEMA_VERSION) {
+        return false;
+    }
+    // 0 -> 1 only added fields, which take their defaults. Later steps that
+    // rename or reinterpret fields go here, oldest first.
+    object.insert("schema_version".to_string(), SCHEMA_VERSION.into());
+    true
+}
+
+/// Returns a copy of the current settings.
+pub(crate) fn current(app: &tauri::AppHandle) -> Settings {
+    app.state::<SettingsState>()
+        .settings
+        .lock()
+        .map(|settings| settings.clone())
+        .unwrap_or_default()
+}
+
+fn settings_path(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    let dir = app
+        .path()
+        .app_config_dir()
+        .map_err(|err| format!("Failed to resolve config dir: {err}"))?;
+    Ok(dir.join(SETTINGS_FILE))
+}
+
+fn write_settings(path: &Path, settings: &Settings) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|err| format!("Failed to create config dir: {err}"))?;
+    }
+    let contents = serde_json::to_string_pretty(settings)
+        .map_err(|err| format!("Failed to serialize settings: {err}"))?;
+    fs::write(path, contents).map_err(|err| format!("Failed to write settings: {err}"))
+}
+
+fn is_absolute(path: &str) -> bool {
+    Path::new(path).is_absolute()
+}
+
+/// Checks every field and reports all problems at once.
+fn validate(settings: &Settings) -> Vec<FieldError> {
+    let mut errors = Vec::new();
+    let mut check = |ok: bool, field: &str, message: &str| {
+        if !ok {
+            errors.push(FieldError {
+                field: field.to_string(),
+                message: message.to_string(),
+            });
+        }
+    };
+
+    check(
+        settings.schema_version <= SCHEMA_VERSION,
+        "schema_version",
+        "Written by a newer version of ghc",
+    );
+    check(
+        settings
+            .copilot_path
+            .as_deref()
+            .map(str::trim)
+            .is_none_or(|path| path.is_empty() || is_absolute(path)),
+        "copilot_path",
+        "Must be an absolute path",
+    );
+    check(
+        settings
+            .homebrew_prefixes
+            .iter()
+            .all(|prefix| is_absolute(prefix.trim())),
+        "homebrew_prefixes",
+        "Every prefix must be an absolute path",
+    );
+    check(
+        settings.probe_timeout_secs > 0,
+        "probe_timeout_secs",
+        "Must be at least 1 second",
+    );
+    let model = settings.default_model.trim();
+    check(
+        !model.is_empty() && !model.starts_with('-'),
+        "default_model",
+        "Must name a model",
+    );
+    check(
+        settings.conversation_max_chars > 0,
+        "conversation_max_chars",
+        "Must be greater than 0",
+    );
+    let mut components = Path::new(settings.temp_dir_name.trim()).components();
+    check(
+        matches!(
+            (components.next(), components.next()),
+            (Some(Component::Normal(_)), None)
+        ),
+        "temp_dir_name",
+        "Must be a single directory name",
+    );
+    let host = settings.github_host.trim();
+    check(
+        !host.is_empty() && !host.contains(char::is_whitespace),
+        "github_host",
+        "Must be a host name such as github.com",
+    );
+    check(
+        settings
+            .github_api_base
+            .as_deref()
+            .map(str::trim)
+            .is_none_or(|base| {
+                base.is_empty() || base.starts_with("https://") || base.starts_with("http://")
+            }),
+        "github_api_base",
+        "Must be an http(s) URL",
+    );
+    check(
+        !settings.oauth_client_id.trim().is_empty(),
+        "oauth_client_id",
+        "Must not be empty",
+    );
+    check(
+        !settings.oauth_scopes.is_empty()
+            && settings
+                .oauth_scopes
+                .iter()
+                .all(|scope| !scope.trim().is_empty() && !scope.contains(char::is_whitespace)),
+        "oauth_scopes",
+        "Must list at least one scope, without spaces",
+    );
+    check(
+        settings
+            .legacy_env_file
+            .as_deref()
+            .map(str::trim)
+            .is_none_or(|path| path.is_empty() || is_absolute(path)),
+        "legacy_env_file",
+        "Must be an absolute path",
+    );
+
+    let policy = &settings.context_policy;
+    check(
+        policy.max_file_bytes > 0,
+        "context_policy.max_file_bytes",
+        "Must be greater than 0",
+    );
+    check(
+        policy.max_total_bytes >= policy.max_file_bytes,
+        "context_policy.max_total_bytes",
+        "Must be at least max_file_bytes",
+    );
+    check(
+        policy.on_oversize != OversizeAction::Truncate
+            || policy
+                .truncate_head_bytes
+                .saturating_add(policy.truncate_tail_bytes)
+                <= policy.max_file_bytes,
+        "context_policy.truncate_head_bytes",
+        "Head and tail together must fit in max_file_bytes",
+    );
+
+    let budget = &settings.usage_budget;
+    let valid_limit =
+        |limit: Option<f64>| limit.is_none_or(|limit| limit.is_finite() && limit >= 0.0);
+    check(
+        valid_limit(budget.soft_limit),
+        "usage_budget.soft_limit",
+        "Must be a number of premium requests, 0 or more",
+    );
+    check(
+        valid_limit(budget.hard_limit),
+        "usage_budget.hard_limit",
+        "Must be a number of premium requests, 0 or more",
+    );
+    check(
+        match (budget.soft_limit, budget.hard_limit) {
+            (Some(soft), Some(hard)) => soft <= hard,
+            _ => true,
+        },
+        "usage_budget.soft_limit",
+        "Must not exceed the hard limit",
+    );
+    errors
+}
+
+/// Replaces every field of `settings` that fails validation with its
+/// default and returns the errors that caused it. A nested field such as
+/// `usage_budget.soft_limit` is reset on its own first; if its group is still
+/// invalid, since the members are checked against each other, the whole
+/// group is reset.
+fn reset_invalid(settings: Settings) -> (Settings, Vec<FieldError>) {
+    let errors = validate(&settings);
+    if errors.is_empty() {
+        return (settings, errors);
+    }
+    let (Ok(mut fields), Ok(defaults)) = (
+        serde_json::to_value(&settings),
+        serde_json::to_value(Settings::default()),
+    ) else {
+        return (Settings::default(), errors);
+    };
+    let reset = |fields: &mut serde_json::Value, field: &str| {
+        let pointer = format!("/{}", field.replace('.', "/"));
+        if let (Some(slot), Some(default)) =
+            (fields.pointer_mut(&pointer), defaults.pointer(&pointer))
+        {
+            *slot = default.clone();
+        }
+    };
+    for error in &errors {
+        reset(&mut fields, &error.field);
+    }
+    let parse = |fields: &serde_json::Value| serde_json::from_value::<Settings>(fields.clone());
+    if let Ok(repaired) = parse(&fields) {
+        let remaining = validate(&repaired);
+        if remaining.is_empty() {
+            return (repaired, errors);
+        }
+        for error in &remaining {
+            reset(
+                &mut fields,
+                error.field.split('.').next().unwrap_or_default(),
+            );
+        }
+    }
+    let repaired = parse(&fields)
+        .ok()
+        .filter(|repaired| validate(repaired).is_empty())
+        .unwrap_or_default();
+    (repaired, errors)
+}
+
+/// Top-level fields that differ between `old` and `new`.
+fn changed_fields(old: &Settings, new: &Settings) -> Vec<String> {
+    let (Ok(serde_json::Value::Object(old)), Ok(serde_json::Value::Object(new))) =
+        (serde_json::to_value(old), serde_json::to_value(new))
+    else {
+        return Vec::new();
+    };
+    new.into_iter()
+        .filter(|(field, value)| old.get(field) != Some(value))
+        .map(|(field, _)| field)
+        .collect()
+}
+
+/// Replaces a stored `auto` credential backend with the one it resolves to,
+/// so tokens are not written to one store and looked for in another as the
+/// keychain comes and goes. Secrets left in the file store while the
+/// keychain was unreachable are moved into it.
+///
+/// Skipped while the stored file is damaged, since its backend may only be
+/// `auto` because the real one failed to parse.
+pub(crate) fn resolve_credential_backend(app: &tauri::AppHandle) -> Result<(), String> {
+    if app.state::<SettingsState>().damaged.load(Ordering::SeqCst) {
+        return Ok(());
+    }
+    let mut settings = current(app);
+    if settings.credential_backend != CredentialBackend::Auto {
+        return Ok(());
+    }
+    let Some(backend) = credentials::resolve_auto(app)? else {
+        return Ok(());
+    };
+    if backend == CredentialBackend::Keyring {
+        credentials::move_secrets(
+            app,
+            CredentialBackend::File,
+            CredentialBackend::Keyring,
+            &accounts::secret_keys(app),
+        )?;
+    }
+    settings.credential_backend = backend;
+    write_settings(&settings_path(app)?, &settings)?;
+    if let Ok(mut current) = app.state::<SettingsState>().settings.lock() {
+        *current = settings;
+    }
+    Ok(())
+}
+
+/// Carries state kept outside the settings over to `new`. Runs before the
+/// settings are saved, so a failure leaves the old ones in place.
+fn prepare_change(app: &tauri::AppHandle, old: &Settings, new: &Settings) -> Result<(), String> {
+    if old.credential_backend != new.credential_backend {
+        credentials::move_secrets(
+            app,
+            old.credential_backend,
+            new.credential_backend,
+            &accounts::secret_keys(app),
+        )?;
+    }
+    Ok(())
+}
+
+/// Drops state derived from the old settings once the new ones are live.
+fn apply_change(app: &tauri::AppHandle, changed: &[String]) {
+    let changed = |field: &str| changed.iter().any(|name| name == field);
+    if changed("copilot_path") || changed("homebrew_prefixes") || changed("default_model") {
+        models::clear_cache(app);
+    }
+}
+
+#[tauri::command]
+pub(crate) fn get_settings(app: tauri::AppHandle) -> Settings {
+    current(&app)
+}
+
+/// Validates and saves `settings`, then emits `settings-changed`.
+#[tauri::command]
+pub(crate) fn update_settings(
+    app: tauri::AppHandle,
+    mut settings: Settings,
+) -> Result<Settings, SettingsError> {
+    let errors = validate(&settings);
+    if !errors.is_empty() {
+        return Err(SettingsError::Invalid { errors });
+    }
+    settings.schema_version = SCHEMA_VERSION;
+    if settings.credential_backend == CredentialBackend::Auto {
+        settings.credential_backend =
+            credentials::resolve_auto(&app)?.unwrap_or(CredentialBackend::File);
+    }
+    let old = current(&app);
+    prepare_change(&app, &old, &settings)?;
+    write_settings(&settings_path(&app)?, &settings)?;
+    let state = app.state::<SettingsState>();
+    if let Ok(mut current) = state.settings.lock() {
+        *current = settings.clone();
+    }
+    state.damaged.store(false, Ordering::SeqCst);
+    let changed = changed_fields(&old, &settings);
+    apply_change(&app, &changed);
+    let _ = app.emit(
+        "settings-changed",
+        SettingsChanged {
+            settings: settings.clone(),
+            changed,
+        },
+    );
+    Ok(settings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn a_wrong_typed_field_keeps_the_others() {
+        let (settings, errors) = parse_fields(json!({
+            "copilot_timeout_secs": "ten minutes",
+            "credential_backend": "file",
+            "default_model": "claude-sonnet-4",
+        }));
+        assert!(settings.credential_backend == CredentialBackend::File);
+        assert_eq!(settings.default_model, "claude-sonnet-4");
+        assert_eq!(settings.copilot_timeout_secs, 600);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].field, "copilot_timeout_secs");
+    }
+
+    #[test]
+    fn a_wrong_typed_credential_backend_is_reported() {
+        let (settings, errors) = parse_fields(json!({
+            "credential_backend": "vault",
+            "probe_timeout_secs": 30,
+        }));
+        assert!(settings.credential_backend == CredentialBackend::Auto);
+        assert_eq!(settings.probe_timeout_secs, 30);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].field, "credential_backend");
+    }
+
+    #[test]
+    fn unknown_fields_are_ignored_and_non_objects_rejected() {
+        let (_, errors) = parse_fields(json!({ "theme": "dark" }));
+        assert!(errors.is_empty());
+        let (_, errors) = parse_fields(json!(["not", "settings"]));
+        assert_eq!(errors.len(), 1);
+    }
+
+    #[test]
+    fn a_soft_limit_above_the_hard_limit_resets_only_the_soft_limit() {
+        let settings = Settings {
+            usage_budget: UsageBudget {
+                soft_limit: Some(50.0),
+                hard_limit: Some(20.0),
+            },
+            ..Settings::default()
+        };
+        let (repaired, errors) = reset_invalid(settings);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].field, "usage_budget.soft_limit");
+        assert_eq!(repaired.usage_budget.soft_limit, None);
+        assert_eq!(repaired.usage_budget.hard_limit, Some(20.0));
+    }
+
+    #[test]
+    fn a_group_still_invalid_after_the_field_reset_is_reset_whole() {
+        let settings = Settings {
+            context_policy: ContextPolicy {
+                max_file_bytes: 8 * 1024 * 1024,
+                ..ContextPolicy::default()
+            },
+            default_model: "gpt-5".to_string(),
+            ..Settings::default()
+        };
+        let (repaired, errors) = reset_invalid(settings);
+        assert!(errors
+            .iter()
+            .any(|error| error.field == "context_policy.max_total_bytes"));
+        assert_eq!(
+            repaired.context_policy.max_file_bytes,
+            ContextPolicy::default().max_file_bytes
+        );
+        assert_eq!(repaired.default_model, "gpt-5");
+    }
+
+    #[test]
+    fn valid_settings_are_left_alone() {
+        let (repaired, errors) = reset_invalid(Settings::default());
+        assert!(errors.is_empty());
+        assert_eq!(repaired.default_model, Settings::default().default_model);
+    }
+}
@@ -0,0 +1,457 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::Manager;
+
+use crate::history::{self, now_millis};
+
+const TEMPLATES_FILE: &str = "templates.json";
+const OPEN: &str = "{{";
+const CLOSE: &str = "}}";
+
+/// A reusable prompt. `{{name}}` placeholders in `body` are filled in by
+/// [`render_template`].
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) struct Template {
+    pub(crate) id: String,
+    pub(crate) name: String,
+    pub(crate) body: String,
+    /// Model to run the rendered prompt with instead of the picker's choice.
+    #[serde(default)]
+    pub(crate) default_model: Option<String>,
+    /// File or folder attached whenever the template is used.
+    #[serde(default)]
+    pub(crate) context_path: Option<String>,
+    /// Unix timestamps in milliseconds.
+    pub(crate) created_at: u64,
+    pub(crate) updated_at: u64,
+}
+
+/// The editable fields of a template.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct TemplateInput {
+    name: String,
+    body: String,
+    #[serde(default)]
+    default_model: Option<String>,
+    #[serde(default)]
+    context_path: Option<String>,
+}
+
+#[derive(Serialize)]
+pub(crate) struct TemplateView {
+    #[serde(flatten)]
+    template: Template,
+    /// Placeholder names in `body`, in order of first use.
+    variables: Vec<String>,
+}
+
+impl From<Template> for TemplateView {
+    fn from(template: Template) -> Self {
+        let variables = variables(&template.body).unwrap_or_default();
+        TemplateView {
+            template,
+            variables,
+        }
+    }
+}
+
+/// A template with its variables filled in, ready for `run_copilot`.
+#[derive(Serialize)]
+pub(crate) struct RenderedTemplate {
+    prompt: String,
+    model: Option<String>,
+    context_paths: Vec<String>,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub(crate) enum TemplateError {
+    NotFound {
+        id: String,
+    },
+    /// `render_template` was not given a value for every placeholder.
+    MissingVariables {
+        missing: Vec<String>,
+    },
+    /// The template's context file no longer exists.
+    ContextMissing {
+        path: String,
+    },
+    /// The template itself is malformed, e.g. an unclosed `{{`.
+    Invalid {
+        message: String,
+    },
+    Failed {
+        message: String,
+    },
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TemplateError::NotFound { id } => write!(f, "Template {id} not found"),
+            TemplateError::MissingVariables { missing } => {
+                write!(f, "Missing values for: {}", missing.join(", "))
+            }
+            TemplateError::ContextMissing { path } => {
+                write!(f, "The template's context file {path} does not exist")
+            }
+            TemplateError::Invalid { message } | TemplateError::Failed { message } => {
+                write!(f, "{message}")
+            }
+        }
+    }
+}
+
+impl From<String> for TemplateError {
+    fn from(message: String) -> Self {
+        TemplateError::Failed { message }
+    }
+}
+
+/// Templates persisted as JSON in the app config dir.
+pub(crate) struct TemplateStore(Mutex<Vec<Template>>);
+
+impl TemplateStore {
+    pub(crate) fn load(app: &tauri::AppHandle) -> Self {
+        let templates = templates_path(app)
+            .ok()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default();
+        TemplateStore(Mutex::new(templates))
+    }
+}
+
+fn templates_path(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    let dir = app
+        .path()
+        .app_config_dir()
+        .map_err(|err| format!("Failed to resolve config dir: {err}"))?;
+    Ok(dir.join(TEMPLATES_FILE))
+}
+
+fn write_templates(app: &tauri::AppHandle, templates: &[Template]) -> Result<(), String> {
+    let path = templates_path(app)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|err| format!("Failed to create config dir: {err}"))?;
+    }
+    let contents = serde_json::to_string_pretty(templates)
+        .map_err(|err| format!("Failed to serialize templates: {err}"))?;
+    fs::write(&path, contents).map_err(|err| format!("Failed to write templates: {err}"))
+}
+
+/// Applies `change` to the stored templates and saves them.
+fn modify<T>(
+    app: &tauri::AppHandle,
+    change: impl FnOnce(&mut Vec<Template>) -> Result<T, TemplateError>,
+) -> Result<T, TemplateError> {
+    let state = app.state::<TemplateStore>();
+    let mut templates = state
+        .0
+        .lock()
+        .map_err(|_| "Template store is poisoned".to_string())?;
+    let mut updated = templates.clone();
+    let result = change(&mut updated)?;
+    write_templates(app, &updated)?;
+    *templates = updated;
+    Ok(result)
+}
+
+fn is_variable_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|ch| ch.is_alphanumeric() || ch == '_' || ch == '-' || ch == '.')
+}
+
+/// Splits `body` into literal text and placeholder names. `{{ name }}` may
+/// pad the name with spaces. Anything else in braces stays literal text:
+/// `{{` that does not wrap a variable name, `${{ … }}` as used by GitHub
+/// Actions, and `\{{`, which renders as `{{`.
+fn parse(body: &str) -> Result<Vec<(&str, Option<&str>)>, String> {
+    let mut parts = Vec::new();
+    // Start of the literal text not yet pushed, and where to look next.
+    let mut text = 0;
+    let mut from = 0;
+    while let Some(found) = body[from..].find(OPEN) {
+        let start = from + found;
+        let after = start + OPEN.len();
+        if body[..start].ends_with('\\') {
+            parts.push((&body[text..start - 1], None));
+            text = start;
+            from = after;
+            continue;
+        }
+        if body[..start].ends_with('$') {
+            from = after;
+            continue;
+        }
+        let end = body[after..]
+            .find(CLOSE)
+            .map(|end| after + end)
+            .ok_or_else(|| {
+                "Unclosed `{{` in template; write `\\{{` for a literal `{{`".to_string()
+            })?;
+        let name = body[after..end].trim();
+        if is_variable_name(name) {
+            parts.push((&body[text..start], Some(name)));
+            text = end + CLOSE.len();
+        }
+        from = end + CLOSE.len();
+    }
+    parts.push((&body[text..], None));
+    Ok(parts)
+}
+
+fn variables(body: &str) -> Result<Vec<String>, String> {
+    let mut names: Vec<String> = Vec::new();
+    for (_, name) in parse(body)? {
+        if let Some(name) = name.filter(|name| !names.iter().any(|known| known == name)) {
+            names.push(name.to_string());
+        }
+    }
+    Ok(names)
+}
+
+fn clean(value: Option<String>) -> Option<String> {
+    value
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+/// Checks `input` and returns it trimmed.
+fn validate(input: TemplateInput) -> Result<TemplateInput, TemplateError> {
+    let invalid = |message: &str| TemplateError::Invalid {
+        message: message.to_string(),
+    };
+    let name = input.name.trim().to_string();
+    if name.is_empty() {
+        return Err(invalid("Template name must not be empty"));
+    }
+    if input.body.trim().is_empty() {
+        return Err(invalid("Template body must not be empty"));
+    }
+    parse(&input.body).map_err(|message| TemplateError::Invalid { message })?;
+    let default_model = clean(input.default_model);
+    if default_model
+        .as_deref()
+        .is_some_and(|model| model.starts_with('-'))
+    {
+        return Err(invalid("Default model must name a model"));
+    }
+    let context_path = clean(input.context_path);
+    if context_path
+        .as_deref()
+        .is_some_and(|path| !Path::new(path).is_absolute())
+    {
+        return Err(invalid("Context file must be an absolute path"));
+    }
+    Ok(TemplateInput {
+        name,
+        body: input.body,
+        default_model,
+        context_path,
+    })
+}
+
+#[tauri::command]
+pub(crate) fn list_templates(
+    store: tauri::State<'_, TemplateStore>,
+) -> Result<Vec<TemplateView>, String> {
+    let templates = store
+        .0
+        .lock()
+        .map_err(|_| "Template store is poisoned".to_string())?;
+    let mut views: Vec<TemplateView> = templates.iter().cloned().map(TemplateView::from).collect();
+    views.sort_by_key(|view| view.template.name.to_lowercase());
+    Ok(views)
+}
+
+#[tauri::command]
+pub(crate) fn create_template(
+    app: tauri::AppHandle,
+    template: TemplateInput,
+) -> Result<TemplateView, TemplateError> {
+    let input = validate(template)?;
+    let now = now_millis();
+    let template = Template {
+        id: history::new_id(),
+        name: input.name,
+        body: input.body,
+        default_model: input.default_model,
+        context_path: input.context_path,
+        created_at: now,
+        updated_at: now,
+    };
+    modify(&app, |templates| {
+        templates.push(template.clone());
+        Ok(())
+    })?;
+    Ok(template.into())
+}
+
+#[tauri::command]
+pub(crate) fn update_template(
+    app: tauri::AppHandle,
+    id: String,
+    template: TemplateInput,
+) -> Result<TemplateView, TemplateError> {
+    let input = validate(template)?;
+    modify(&app, |templates| {
+        let template = templates
+            .iter_mut()
+            .find(|template| template.id == id)
+            .ok_or_else(|| TemplateError::NotFound { id: id.clone() })?;
+        template.name = input.name;
+        template.body = input.body;
+        template.default_model = input.default_model;
+        template.context_path = input.context_path;
+        template.updated_at = now_millis();
+        Ok(template.clone().into())
+    })
+}
+
+#[tauri::command]
+pub(crate) fn delete_template(app: tauri::AppHandle, id: String) -> Result<(), TemplateError> {
+    modify(&app, |templates| {
+        let before = templates.len();
+        templates.retain(|template| template.id != id);
+        if templates.len() == before {
+            return Err(TemplateError::NotFound { id: id.clone() });
+        }
+        Ok(())
+    })
+}
+
+/// Fills in template `id` with `vars`. Every placeholder needs a non-blank
+/// value; all missing ones are reported together.
+#[tauri::command]
+pub(crate) fn render_template(
+    store: tauri::State<'_, TemplateStore>,
+    id: String,
+    vars: HashMap<String, String>,
+) -> Result<RenderedTemplate, TemplateError> {
+    let template = store
+        .0
+        .lock()
+        .map_err(|_| "Template store is poisoned".to_string())?
+        .iter()
+        .find(|template| template.id == id)
+        .cloned()
+        .ok_or(TemplateError::NotFound { id })?;
+    let parts = parse(&template.body).map_err(|message| TemplateError::Invalid { message })?;
+
+    let value_of = |name: &str| {
+        vars.get(name)
+            .map(String::as_str)
+            .filter(|value| !value.trim().is_empty())
+    };
+    let mut missing: Vec<String> = Vec::new();
+    for name in parts.iter().filter_map(|(_, name)| *name) {
+        if value_of(name).is_none() && !missing.iter().any(|known| known == name) {
+            missing.push(name.to_string());
+        }
+    }
+    if !missing.is_empty() {
+        return Err(TemplateError::MissingVariables { missing });
+    }
+    if let Some(path) = template
+        .context_path
+        .as_ref()
+        .filter(|path| !Path::new(path).exists())
+    {
+        return Err(TemplateError::ContextMissing { path: path.clone() });
+    }
+
+    let mut prompt = String::new();
+    for (text, name) in parts {
+        prompt.push_str(text);
+        if let Some(value) = name.and_then(value_of) {
+            prompt.push_str(value);
+        }
+    }
+    Ok(RenderedTemplate {
+        prompt,
+        model: template.default_model,
+        context_paths: template.context_path.into_iter().collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_splits_text_and_placeholders() {
+        assert_eq!(
+            parse("Hi {{ name }}, see {{file.path}}.").unwrap(),
+            vec![
+                ("Hi ", Some("name")),
+                (", see ", Some("file.path")),
+                (".", None)
+            ]
+        );
+        assert_eq!(
+            parse("fn main() { }").unwrap(),
+            vec![("fn main() { }", None)]
+        );
+    }
+
+    #[test]
+    fn parse_rejects_unclosed_placeholders() {
+        assert!(parse("Hi {{name").is_err());
+        assert!(parse("{{a}} and {{b").is_err());
+    }
+
+    #[test]
+    fn braces_around_anything_but_a_name_stay_literal() {
+        assert_eq!(
+            parse(r#"println!("{}", format!("{{}}"));"#).unwrap(),
+            vec![(r#"println!("{}", format!("{{}}"));"#, None)]
+        );
+        assert_eq!(
+            parse("{{  }} {{two words}} {{ name }}").unwrap(),
+            vec![("{{  }} {{two words}} ", Some("name")), ("", None)]
+        );
+    }
+
+    #[test]
+    fn github_actions_expressions_stay_literal() {
+        assert_eq!(
+            parse("on ${{ github.ref }} for {{ branch }}").unwrap(),
+            vec![("on ${{ github.ref }} for ", Some("branch")), ("", None)]
+        );
+    }
+
+    #[test]
+    fn a_backslash_escapes_the_opening_braces() {
+        let parts = parse(r"Write \{{name}} to get {{name}}.").unwrap();
+        assert_eq!(
+            parts,
+            vec![
+                ("Write ", None),
+                ("{{name}} to get ", Some("name")),
+                (".", None)
+            ]
+        );
+        assert_eq!(
+            parse(r"unclosed \{{ is fine").unwrap(),
+            vec![("unclosed ", None), ("{{ is fine", None)]
+        );
+    }
+
+    #[test]
+    fn variables_are_listed_once_in_order_of_first_use() {
+        assert_eq!(
+            variables("{{b}} {{a}} {{ b }} ${{ c }} \\{{d}}").unwrap(),
+            vec!["b".to_string(), "a".to_string()]
+        );
+        assert!(variables("no placeholders").unwrap().is_empty());
+    }
+}
@@ -0,0 +1,436 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tauri::Manager;
+
+use crate::{accounts, history, models, settings};
+
+const LEDGER_FILE: &str = "usage.jsonl";
+
+/// Monthly limits on premium requests. GitHub resets the allowance at the
+/// start of each month in UTC, so months here are UTC months too.
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct UsageBudget {
+    /// Premium requests after which runs on premium models still go ahead
+    /// but come back with a warning.
+    pub(crate) soft_limit: Option<f64>,
+    /// Premium requests after which runs on premium models are refused.
+    pub(crate) hard_limit: Option<f64>,
+}
+
+/// One Copilot run that may have been billed.
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) struct UsageEntry {
+    /// Unix timestamp in milliseconds.
+    pub(crate) at: u64,
+    pub(crate) model: String,
+    /// Premium requests the run counted as.
+    pub(crate) multiplier: f64,
+    #[serde(default)]
+    pub(crate) account: Option<String>,
+    pub(crate) status: history::TurnStatus,
+}
+
+/// Append-only JSON-lines ledger under `<app data dir>/usage.jsonl`.
+pub(crate) struct UsageLedger {
+    path: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl UsageLedger {
+    pub(crate) fn new(app: &tauri::AppHandle) -> Result<Self, String> {
+        let path = app
+            .path()
+            .app_data_dir()
+            .map_err(|err| format!("Failed to resolve data dir: {err}"))?
+            .join(LEDGER_FILE);
+        Ok(UsageLedger {
+            path,
+            lock: Mutex::new(()),
+        })
+    }
+
+    fn append(&self, entry: &UsageEntry) -> Result<(), String> {
+        let line = serde_json::to_string(entry)
+            .map_err(|err| format!("Failed to serialize usage: {err}"))?;
+        let _guard = self.lock.lock().map_err(|_| "Usage ledger is poisoned")?;
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|err| format!("Failed to create data dir: {err}"))?;
+        }
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(|err| format!("Failed to open usage ledger: {err}"))?;
+        writeln!(file, "{line}").map_err(|err| format!("Failed to write usage: {err}"))
+    }
+
+    fn entries(&self) -> Vec<UsageEntry> {
+        let Ok(_guard) = self.lock.lock() else {
+            return Vec::new();
+        };
+        fs::read_to_string(&self.path)
+            .map(|contents| {
+                contents
+                    .lines()
+                    .filter_map(|line| serde_json::from_str(line).ok())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+/// UTC month of `millis` as `YYYY-MM`.
+fn month_key(millis: u64) -> String {
+    history::utc_date(millis)[..7].to_string()
+}
+
+/// Premium requests a run on `model` counts as. Models missing from the
+/// catalog are charged as one request rather than assumed free.
+fn multiplier(app: &tauri::AppHandle, model: &str) -> f64 {
+    models::available(app)
+        .models
+        .into_iter()
+        .find(|info| info.id == model)
+        .and_then(|info| info.multiplier)
+        .unwrap_or(1.0)
+}
+
+/// Account a run is attributed to, matching the token it runs with.
+fn account_for(app: &tauri::AppHandle, account: Option<&str>) -> Option<String> {
+    attributed_account(
+        account,
+        crate::env_github_token().is_some(),
+        accounts::active_login(app),
+    )
+}
+
+/// The account a run asked for; otherwise none when `GITHUB_TOKEN` supplies
+/// the token, else the active account.
+fn attributed_account(
+    account: Option<&str>,
+    env_token: bool,
+    active: Option<String>,
+) -> Option<String> {
+    match account {
+        Some(account) => Some(account.to_string()),
+        None if env_token => None,
+        None => active,
+    }
+}
+
+/// Adds `turn` to the ledger. Runs refused or failed before Copilot started
+/// are skipped; anything that reached Copilot may have been billed.
+pub(crate) fn record(app: &tauri::AppHandle, turn: &history::Turn) {
+    if matches!(
+        turn.status,
+        history::TurnStatus::Refused | history::TurnStatus::Error
+    ) {
+        return;
+    }
+    let entry = UsageEntry {
+        at: turn.started_at,
+        model: turn.model.clone(),
+        multiplier: multiplier(app, &turn.model),
+        account: account_for(app, turn.account.as_deref()),
+        status: turn.status,
+    };
+    if let Err(err) = app.state::<UsageLedger>().append(&entry) {
+        crate::diagnostics::record_error(app, "usage", &err);
+    }
+}
+
+fn month_total(entries: &[UsageEntry], month: &str, account: Option<&str>) -> f64 {
+    entries
+        .iter()
+        .filter(|entry| entry.account.as_deref() == account && month_key(entry.at) == month)
+        .map(|entry| entry.multiplier)
+        .sum()
+}
+
+pub(crate) enum BudgetCheck {
+    Allowed,
+    /// The soft limit is used up; the run may go ahead.
+    Warn {
+        used: f64,
+        limit: f64,
+    },
+    /// The hard limit is used up; the run must not start.
+    Block {
+        used: f64,
+        limit: f64,
+    },
+}
+
+/// Checks a run on `model` against this month's budget. Free models are
+/// always allowed; a run started just below a limit may end up past it.
+pub(crate) fn check_budget(
+    app: &tauri::AppHandle,
+    model: &str,
+    account: Option<&str>,
+) -> BudgetCheck {
+    let budget = settings::current(app).usage_budget;
+    let cost = multiplier(app, model);
+    if cost <= 0.0 || (budget.soft_limit.is_none() && budget.hard_limit.is_none()) {
+        return BudgetCheck::Allowed;
+    }
+    let account = account_for(app, account);
+    let entries = app.state::<UsageLedger>().entries();
+    let used = month_total(
+        &entries,
+        &month_key(history::now_millis()),
+        account.as_deref(),
+    );
+    budget_check(cost, used, &budget)
+}
+
+/// What a run costing `cost` may do with `used` premium requests spent.
+fn budget_check(cost: f64, used: f64, budget: &UsageBudget) -> BudgetCheck {
+    if cost <= 0.0 {
+        return BudgetCheck::Allowed;
+    }
+    match budget_state(used, budget) {
+        BudgetState::Exceeded => BudgetCheck::Block {
+            used,
+            limit: budget.hard_limit.unwrap_or_default(),
+        },
+        BudgetState::Warning => BudgetCheck::Warn {
+            used,
+            limit: budget.soft_limit.unwrap_or_default(),
+        },
+        BudgetState::Ok => BudgetCheck::Allowed,
+    }
+}
+
+#[derive(Serialize)]
+pub(crate) struct UsageBucket {
+    /// `YYYY-MM-DD`, `YYYY-MM` or a model id, depending on the list.
+    pub(crate) key: String,
+    pub(crate) premium_requests: f64,
+    pub(crate) runs: usize,
+}
+
+#[derive(Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum BudgetState {
+    Ok,
+    /// At or past the soft limit.
+    Warning,
+    /// At or past the hard limit.
+    Exceeded,
+}
+
+/// Where `used` premium requests stand against `budget`. Both the check
+/// before a run and the summary use this, so the UI shows "exceeded" exactly
+/// when runs are refused.
+fn budget_state(used: f64, budget: &UsageBudget) -> BudgetState {
+    match (budget.hard_limit, budget.soft_limit) {
+        (Some(limit), _) if used >= limit => BudgetState::Exceeded,
+        (_, Some(limit)) if used >= limit => BudgetState::Warning,
+        _ => BudgetState::Ok,
+    }
+}
+
+#[derive(Serialize)]
+pub(crate) struct UsageSummary {
+    pub(crate) month: String,
+    pub(crate) account: Option<String>,
+    pub(crate) premium_requests: f64,
+    pub(crate) runs: usize,
+    /// Days of `month` that had runs, oldest first.
+    pub(crate) days: Vec<UsageBucket>,
+    /// Every month in the ledger, oldest first.
+    pub(crate) months: Vec<UsageBucket>,
+    /// Models used in `month`, most premium requests first.
+    pub(crate) models: Vec<UsageBucket>,
+    pub(crate) budget: UsageBudget,
+    pub(crate) budget_state: BudgetState,
+}
+
+fn buckets<'a>(entries: impl Iterator<Item = (String, &'a UsageEntry)>) -> Vec<UsageBucket> {
+    let mut totals: BTreeMap<String, (f64, usize)> = BTreeMap::new();
+    for (key, entry) in entries {
+        let total = totals.entry(key).or_default();
+        total.0 += entry.multiplier;
+        total.1 += 1;
+    }
+    totals
+        .into_iter()
+        .map(|(key, (premium_requests, runs))| UsageBucket {
+            key,
+            premium_requests,
+            runs,
+        })
+        .collect()
+}
+
+/// Aggregates the ledger for `month` (`YYYY-MM`, the current UTC month when
+/// omitted) and `account` (the active account when omitted).
+#[tauri::command]
+pub(crate) async fn get_usage_summary(
+    app: tauri::AppHandle,
+    month: Option<String>,
+    account: Option<String>,
+) -> Result<UsageSummary, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let month = month.unwrap_or_else(|| month_key(history::now_millis()));
+        let account = account_for(&app, account.as_deref());
+        let entries: Vec<UsageEntry> = app
+            .state::<UsageLedger>()
+            .entries()
+            .into_iter()
+            .filter(|entry| entry.account == account)
+            .collect();
+        let in_month: Vec<&UsageEntry> = entries
+            .iter()
+            .filter(|entry| month_key(entry.at) == month)
+            .collect();
+
+        let premium_requests = in_month.iter().map(|entry| entry.multiplier).sum();
+        let days = buckets(
+            in_month
+                .iter()
+                .map(|entry| (history::utc_date(entry.at), *entry)),
+        );
+        let months = buckets(entries.iter().map(|entry| (month_key(entry.at), entry)));
+        let mut models = buckets(in_month.iter().map(|entry| (entry.model.clone(), *entry)));
+        models.sort_by(|a, b| b.premium_requests.total_cmp(&a.premium_requests));
+
+        let budget = settings::current(&app).usage_budget;
+        let budget_state = budget_state(premium_requests, &budget);
+        Ok(UsageSummary {
+            month,
+            account,
+            premium_requests,
+            runs: in_month.len(),
+            days,
+            months,
+            models,
+            budget,
+            budget_state,
+        })
+    })
+    .await
+    .map_err(|err| format!("Failed to summarise usage: {err}"))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 2024-03-15 12:00:00 UTC and 2024-04-01 00:00:00 UTC.
+    const MARCH: u64 = 1_710_504_000_000;
+    const APRIL: u64 = 1_711_929_600_000;
+
+    fn entry(at: u64, multiplier: f64, account: Option<&str>) -> UsageEntry {
+        UsageEntry {
+            at,
+            model: "gpt-5".to_string(),
+            multiplier,
+            account: account.map(str::to_string),
+            status: history::TurnStatus::Success,
+        }
+    }
+
+    fn budget(soft_limit: Option<f64>, hard_limit: Option<f64>) -> UsageBudget {
+        UsageBudget {
+            soft_limit,
+            hard_limit,
+        }
+    }
+
+    #[test]
+    fn month_key_uses_the_utc_month() {
+        assert_eq!(month_key(MARCH), "2024-03");
+        assert_eq!(month_key(APRIL - 1), "2024-03");
+        assert_eq!(month_key(APRIL), "2024-04");
+    }
+
+    #[test]
+    fn month_total_counts_only_the_month_and_account() {
+        let entries = vec![
+            entry(MARCH, 1.0, Some("octocat")),
+            entry(APRIL - 1, 0.5, Some("octocat")),
+            entry(APRIL, 1.0, Some("octocat")),
+            entry(MARCH, 10.0, Some("hubot")),
+            entry(MARCH, 3.0, None),
+        ];
+        assert_eq!(month_total(&entries, "2024-03", Some("octocat")), 1.5);
+        assert_eq!(month_total(&entries, "2024-04", Some("octocat")), 1.0);
+        assert_eq!(month_total(&entries, "2024-03", Some("hubot")), 10.0);
+        assert_eq!(month_total(&entries, "2024-03", None), 3.0);
+        assert_eq!(month_total(&entries, "2024-05", Some("octocat")), 0.0);
+    }
+
+    #[test]
+    fn budget_state_treats_limits_as_inclusive() {
+        let limits = budget(Some(5.0), Some(10.0));
+        assert!(matches!(budget_state(4.9, &limits), BudgetState::Ok));
+        assert!(matches!(budget_state(5.0, &limits), BudgetState::Warning));
+        assert!(matches!(budget_state(9.9, &limits), BudgetState::Warning));
+        assert!(matches!(budget_state(10.0, &limits), BudgetState::Exceeded));
+        assert!(matches!(budget_state(12.0, &limits), BudgetState::Exceeded));
+    }
+
+    #[test]
+    fn budget_state_handles_missing_limits() {
+        assert!(matches!(
+            budget_state(100.0, &budget(None, None)),
+            BudgetState::Ok
+        ));
+        assert!(matches!(
+            budget_state(3.0, &budget(Some(3.0), None)),
+            BudgetState::Warning
+        ));
+        assert!(matches!(
+            budget_state(3.0, &budget(None, Some(3.0))),
+            BudgetState::Exceeded
+        ));
+    }
+
+    #[test]
+    fn budget_check_blocks_at_the_hard_limit_and_warns_at_the_soft_one() {
+        let limits = budget(Some(5.0), Some(10.0));
+        assert!(matches!(
+            budget_check(1.0, 4.0, &limits),
+            BudgetCheck::Allowed
+        ));
+        assert!(matches!(
+            budget_check(1.0, 5.0, &limits),
+            BudgetCheck::Warn { used, limit } if used == 5.0 && limit == 5.0
+        ));
+        assert!(matches!(
+            budget_check(1.0, 10.0, &limits),
+            BudgetCheck::Block { used, limit } if used == 10.0 && limit == 10.0
+        ));
+    }
+
+    #[test]
+    fn budget_check_always_allows_free_models() {
+        let limits = budget(Some(5.0), Some(10.0));
+        assert!(matches!(
+            budget_check(0.0, 50.0, &limits),
+            BudgetCheck::Allowed
+        ));
+    }
+
+    #[test]
+    fn runs_on_the_env_token_are_not_charged_to_the_active_account() {
+        let active = || Some("octocat".to_string());
+        assert_eq!(attributed_account(None, true, active()), None);
+        assert_eq!(
+            attributed_account(None, false, active()),
+            Some("octocat".to_string())
+        );
+        assert_eq!(
+            attributed_account(Some("hubot"), true, active()),
+            Some("hubot".to_string())
+        );
+        assert_eq!(attributed_account(None, false, None), None);
+    }
+}
@@ -1,6 +1,6 @@
 import { invoke } from "@tauri-apps/api/core";
 import { listen } from "@tauri-apps/api/event";
-import { open } from "@tauri-apps/plugin-dialog";
+import { open, save } from "@tauri-apps/plugin-dialog";
 import { openUrl } from "@tauri-apps/plugin-opener";
 import DOMPurify from "dompurify";
 import { marked } from "marked";
@@ -10,8 +10,14 @@ window.addEventListener("DOMContentLoaded", () => {
   const inputEl = document.querySelector<HTMLInputElement>("#message-input");
   const modelSelect =
     document.querySelector<HTMLSelectElement>("#model-select");
+  const templateSelect =
+    document.querySelector<HTMLSelectElement>("#template-select");
+  const templateVarsEl =
+    document.querySelector<HTMLElement>("#template-vars");
   const fileButton =
     document.querySelector<HTMLButtonElement>("#file-picker");
+  const folderButton =
+    document.querySelector<HTMLButtonElement>("#folder-picker");
   const fileContextEl =
     document.querySelector<HTMLElement>("#file-context");
   const sendButton =
@@ -38,6 +44,28 @@ window.addEventListener("DOMContentLoaded", () => {
     })();
   const installCopilotButton =
     document.querySelector<HTMLButtonElement>("#install-copilot");
+  const upgradeCopilotButton =
+    document.querySelector<HTMLButtonElement>("#upgrade-copilot");
+  const diagnosticsButton = document.querySelector<HTMLButtonElement>(
+    "#collect-diagnostics",
+  );
+  const exportFormatSelect =
+    document.querySelector<HTMLSelectElement>("#export-format");
+  const exportButton = document.querySelector<HTMLButtonElement>(
+    "#export-conversation",
+  );
+  const importButton = document.querySelector<HTMLButtonElement>(
+    "#import-conversation",
+  );
+  const newConversationButton = document.querySelector<HTMLButtonElement>(
+    "#new-conversation",
+  );
+  const deleteConversationButton = document.querySelector<HTMLButtonElement>(
+    "#delete-conversation",
+  );
+  const conversationStatusEl = document.querySelector<HTMLElement>(
+    "#conversation-status",
+  );
   const reloadAppButton =
     document.querySelector<HTMLButtonElement>("#reload-app");
   const copyWrap =
@@ -87,18 +115,52 @@ window.addEventListener("DOMContentLoaded", () => {
       document.querySelector(".output-wrap")?.appendChild(created);
       return created;
     })();
+  const historySearchEl =
+    document.querySelector<HTMLInputElement>("#history-search");
+  const historyResultsEl =
+    document.querySelector<HTMLElement>("#history-results");
   const authLabel = document.querySelector<HTMLElement>("#auth-label");
+  const accountSelect =
+    document.querySelector<HTMLSelectElement>("#account-select");
   let hasToken = false;
   let lastOutput = "";
   let copyFeedbackTimer: number | undefined;
   let isRunning = false;
-  let contextPath: string | null = null;
-  let contextName: string | null = null;
+  let activeRequestId: string | null = null;
+  let activeLoginId: string | null = null;
+  let loginUserCode = "";
+  let conversationId = crypto.randomUUID();
+  // Successful turns in the current conversation; follow-ups replay them.
+  let conversationTurns = 0;
+  // Earlier turns the last run actually sent along with its prompt.
+  let replayedTurns = 0;
+  // Whether any turn of the current conversation was written to history.
+  let conversationStored = false;
+  let contextPaths: string[] = [];
+  const contextNameOf = (path: string) =>
+    path.split(/[\\/]/).filter(Boolean).pop() ?? path;
+  const renderContext = () => {
+    if (!fileContextEl) return;
+    if (contextPaths.length === 0) {
+      fileContextEl.textContent = "";
+      fileContextEl.title = "";
+      fileContextEl.classList.add("is-hidden");
+      return;
+    }
+    const names = contextPaths.map(contextNameOf);
+    fileContextEl.textContent =
+      names.length > 3
+        ? `Context: ${names.slice(0, 3).join(", ")} (+${names.length - 3})`
+        : `Context: ${names.join(", ")}`;
+    fileContextEl.title = contextPaths.join("\n");
+    fileContextEl.classList.remove("is-hidden");
+  };
   const setPromptEnabled = (enabled: boolean) => {
     inputEl && (inputEl.disabled = !enabled);
     sendButton && (sendButton.disabled = !enabled);
     modelSelect && (modelSelect.disabled = !enabled);
     fileButton && (fileButton.disabled = !enabled);
+    folderButton && (folderButton.disabled = !enabled);
   };
   const setCopyVisible = (visible: boolean) => {
     copyWrap.hidden = !visible;
@@ -115,9 +177,20 @@ window.addEventListener("DOMContentLoaded", () => {
   const historyTooltip = historyToggle
     ?.closest(".icon-wrap")
     ?.querySelector<HTMLElement>(".icon-tooltip");
+  const setSearchResultsVisible = (visible: boolean) => {
+    if (!historyResultsEl) return;
+    historyResultsEl.hidden = !visible;
+    historyResultsEl.classList.toggle("is-hidden", !visible);
+  };
   const setHistoryVisible = (visible: boolean) => {
     historyEl.hidden = !visible;
     historyEl.classList.toggle("is-hidden", !visible);
+    if (historySearchEl) {
+      historySearchEl.hidden = !visible;
+      historySearchEl.classList.toggle("is-hidden", !visible);
+      if (!visible) historySearchEl.value = "";
+    }
+    setSearchResultsVisible(false);
     historyToggle.setAttribute(
       "aria-label",
       visible ? "Hide history" : "Show history",
@@ -132,6 +205,58 @@ window.addEventListener("DOMContentLoaded", () => {
   };
   setHistoryVisible(false);
 
+  type ContextReport = {
+    files: { path: string; decision: string; warnings: string[] }[];
+    total_bytes: number;
+    estimated_tokens: number;
+    warnings: string[];
+    refused?: string | null;
+  };
+
+  const showContextWarnings = (report?: ContextReport | null) => {
+    if (!report) return;
+    const warnings = [
+      ...report.files.flatMap((file) => file.warnings),
+      ...report.warnings,
+    ];
+    if (warnings.length === 0) return;
+    if (statusTimer) window.clearTimeout(statusTimer);
+    authStatusEl.textContent = warnings.join(" ");
+    statusTimer = window.setTimeout(() => {
+      authStatusEl.textContent = "";
+    }, 10_000);
+  };
+
+  const renderConversationStatus = () => {
+    if (!conversationStatusEl) return;
+    if (conversationTurns === 0) {
+      conversationStatusEl.textContent = "";
+      conversationStatusEl.title = "";
+      conversationStatusEl.classList.add("is-hidden");
+      return;
+    }
+    conversationStatusEl.textContent = `Follow-up · ${conversationTurns} earlier turn${conversationTurns === 1 ? "" : "s"}`;
+    conversationStatusEl.title =
+      (replayedTurns > 0
+        ? `The last prompt replayed ${replayedTurns} earlier turn${replayedTurns === 1 ? "" : "s"}. `
+        : "") +
+      "Earlier turns are sent with every prompt. Start a new conversation to send prompts on their own.";
+    conversationStatusEl.classList.remove("is-hidden");
+  };
+
+  const startConversation = () => {
+    conversationId = crypto.randomUUID();
+    conversationTurns = 0;
+    replayedTurns = 0;
+    conversationStored = false;
+    historyEl.replaceChildren();
+    outputEl.textContent = "";
+    lastOutput = "";
+    setCopyVisible(false);
+    copyButton.classList.remove("is-copied");
+    renderConversationStatus();
+  };
+
   const appendHistory = async (promptText: string, outputText: string) => {
     const item = document.createElement("article");
     item.classList.add("history-item");
@@ -147,7 +272,93 @@ window.addEventListener("DOMContentLoaded", () => {
     historyEl.appendChild(item);
   };
 
+  type Turn = {
+    id: string;
+    prompt: string;
+    context_files: string[];
+    output: string;
+    status: string;
+  };
+
+  const loadHistory = async () => {
+    try {
+      const summaries = await invoke<{ id: string; updated_at: number }[]>(
+        "list_conversations",
+      );
+      // Oldest first so the panel reads top to bottom like a live session.
+      for (const summary of [...summaries].reverse()) {
+        const conversation = await invoke<{ id: string; turns: Turn[] }>(
+          "load_conversation",
+          { id: summary.id },
+        );
+        for (const turn of conversation.turns) {
+          if (turn.status !== "success" || !turn.output.trim()) continue;
+          const label = turn.context_files.map((name) => `./${name}`).join(" ");
+          await appendHistory(
+            label ? `${turn.prompt} ${label}` : turn.prompt,
+            turn.output,
+          );
+        }
+      }
+    } catch {
+      // History is best effort; an unreadable store should not block the UI.
+    }
+  };
+
+  const refreshAccounts = async () => {
+    if (!accountSelect) return;
+    try {
+      const accounts = await invoke<{ login: string; active: boolean }[]>(
+        "list_accounts",
+      );
+      accountSelect.replaceChildren(
+        ...accounts.map((account) => {
+          const option = document.createElement("option");
+          option.value = account.login;
+          option.textContent = account.login;
+          option.selected = account.active;
+          return option;
+        }),
+      );
+      // A single account needs no picker.
+      accountSelect.hidden = accounts.length < 2;
+    } catch {
+      accountSelect.hidden = true;
+    }
+  };
+
+  const loadModels = async (refresh = false) => {
+    if (!modelSelect) return;
+    try {
+      const list = await invoke<{
+        source: string;
+        models: { id: string; multiplier: number | null; default: boolean }[];
+      }>("list_models", { refresh });
+      if (list.models.length === 0) return;
+      const current = modelSelect.value;
+      modelSelect.replaceChildren(
+        ...list.models.map((model) => {
+          const option = document.createElement("option");
+          option.value = model.id;
+          option.textContent =
+            model.multiplier === null
+              ? model.id
+              : `${model.id} (${model.multiplier}x)`;
+          option.selected = model.default;
+          return option;
+        }),
+      );
+      // On refresh, keep the user's choice when it is still offered.
+      if (refresh && list.models.some((model) => model.id === current)) {
+        modelSelect.value = current;
+      }
+    } catch {
+      // Leave the picker empty; runs then use the default_model setting.
+    }
+  };
+
   const updateTokenStatus = async () => {
+    void refreshAccounts();
     try {
       const status = await invoke<{ has_token: boolean; tail?: string }>(
         "get_token_status",
@@ -182,14 +393,71 @@ window.addEventListener("DOMContentLoaded", () => {
     }
   };
 
-  void listen<{ status: "ok" | "error"; message: string }>(
+  const verifyToken = async () => {
+    try {
+      const info = await invoke<{
+        login: string;
+        expires_at?: string | null;
+        copilot_access?: boolean | null;
+      }>("verify_github_token", {
+        account: accountSelect?.value || undefined,
+      });
+      if (info.copilot_access === false) {
+        authStatusEl.textContent = `${info.login} has no Copilot access.`;
+      } else if (info.expires_at) {
+        authButton?.setAttribute(
+          "title",
+          `Logout ${info.login} (token expires ${info.expires_at})`,
+        );
+      }
+    } catch (error) {
+      const tokenError = error as { kind?: string; missing?: string[] };
+      if (tokenError?.kind === "revoked") {
+        authStatusEl.textContent =
+          "GitHub rejected the token. Log out and log in again.";
+      } else if (tokenError?.kind === "missing_scopes") {
+        authStatusEl.textContent = `Token is missing ${tokenError.missing?.join(", ")}. Log in again.`;
+      }
+      // Network failures are left to the next Copilot run to report.
+    }
+  };
+
+  void listen<{
+    login_id: string;
+    remaining_secs: number;
+    interval_secs: number;
+  }>("github-login-progress", (event) => {
+    if (event.payload.login_id !== activeLoginId) return;
+    const { remaining_secs } = event.payload;
+    const minutes = Math.floor(remaining_secs / 60);
+    const seconds = String(remaining_secs % 60).padStart(2, "0");
+    authStatusEl.textContent = `Enter code ${loginUserCode} in the browser (${minutes}:${seconds} left). Click again to cancel.`;
+  });
+
+  void listen<{
+    login_id: string;
+    status: "ok" | "error" | "cancelled";
+    message: string;
+    error?: { kind: string } | null;
+  }>(
     "github-login-complete",
     (event) => {
-      const { status, message } = event.payload;
-      authStatusEl.textContent =
-        status === "ok" ? message : `Login failed: ${message}`;
+      const { login_id, status, message, error } = event.payload;
+      // A replaced login reports its own completion; only the latest counts.
+      if (login_id !== activeLoginId) return;
+      activeLoginId = null;
+      if (error?.kind === "expired") {
+        authStatusEl.textContent =
+          "The login code expired. Click Login to get a new one.";
+      } else if (error?.kind === "network") {
+        authStatusEl.textContent = `Could not reach GitHub: ${message}`;
+      } else {
+        authStatusEl.textContent =
+          status === "error" ? `Login failed: ${message}` : message;
+      }
       if (status === "ok") {
         void updateTokenStatus();
+        void verifyToken();
         if (statusTimer) window.clearTimeout(statusTimer);
         statusTimer = window.setTimeout(() => {
           authStatusEl.textContent = "";
@@ -201,15 +469,24 @@ window.addEventListener("DOMContentLoaded", () => {
   formEl?.addEventListener("submit", async (event) => {
     event.preventDefault();
     if (!inputEl) return;
-    if (isRunning) return;
+    if (isRunning) {
+      if (activeRequestId) {
+        outputEl.textContent = "Cancelling...";
+        await invoke("cancel_copilot", { requestId: activeRequestId }).catch(
+          () => undefined,
+        );
+      }
+      return;
+    }
 
     const prompt = inputEl.value.trim();
     if (!prompt) return;
-    const model = modelSelect?.value ?? "claude-sonnet-4.5";
-    if (contextPath && !contextName) {
-      contextName = contextPath.split(/[\\/]/).pop() ?? contextPath;
-    }
-    const contextLabel = contextName ? `./${contextName}` : null;
+    // An empty model makes the backend use the default_model setting.
+    const model = modelSelect?.value ?? "";
+    const contextLabel =
+      contextPaths.length > 0
+        ? contextPaths.map((path) => `./${contextNameOf(path)}`).join(" ")
+        : null;
     const promptForHistory = contextLabel
       ? `${prompt} ${contextLabel}`
       : prompt;
@@ -218,23 +495,50 @@ window.addEventListener("DOMContentLoaded", () => {
     if (sendButton) sendButton.classList.add("is-loading");
     inputEl.disabled = true;
     if (modelSelect) modelSelect.disabled = true;
-    if (sendButton) sendButton.disabled = true;
+    if (sendButton) {
+      sendButton.setAttribute("aria-label", "Cancel");
+      sendButton.setAttribute("title", "Cancel");
+    }
     outputEl.textContent = "Running copilot...";
     setCopyVisible(false);
     copyButton.classList.remove("is-copied");
+    const requestId = crypto.randomUUID();
+    activeRequestId = requestId;
+    let streamed = "";
+    const unlistenOutput = await listen<{ request_id: string; chunk: string }>(
+      "copilot-output",
+      async (event) => {
+        if (event.payload.request_id !== requestId) return;
+        streamed += event.payload.chunk;
+        outputEl.innerHTML = DOMPurify.sanitize(await marked.parse(streamed));
+      },
+    );
     try {
       const result = await invoke<{
         output: string;
-        temp_path?: string | null;
-        context_path?: string | null;
+        staging_dir?: string | null;
+        context_paths: string[];
+        prior_turns: number;
+        context_report?: ContextReport | null;
+        budget_warning?: string | null;
       }>("run_copilot", {
         args: {
+          requestId,
+          conversationId,
           prompt,
           model,
-          contextPath: contextPath ?? undefined,
+          contextPaths,
+          account: accountSelect?.value || undefined,
         },
       });
       lastOutput = result.output ?? "";
+      conversationTurns += 1;
+      replayedTurns = result.prior_turns ?? 0;
+      renderConversationStatus();
+      showContextWarnings(result.context_report);
+      if (result.budget_warning) {
+        authStatusEl.textContent = result.budget_warning;
+      }
       const rendered = result
         ? DOMPurify.sanitize(await marked.parse(result.output))
         : "(no output)";
@@ -253,30 +557,59 @@ window.addEventListener("DOMContentLoaded", () => {
     } catch (error) {
       lastOutput = "";
       setCopyVisible(false);
-      outputEl.textContent =
-        error instanceof Error
-          ? error.message
-          : String(error ?? "Unknown error");
+      const copilotError = error as { kind?: string; message?: string };
+      if (copilotError?.kind === "cancelled") {
+        outputEl.textContent = "Cancelled.";
+      } else if (copilotError?.kind === "context_refused") {
+        const refused = error as { reason: string; report: ContextReport };
+        outputEl.textContent = `Context refused: ${refused.reason}`;
+        showContextWarnings(refused.report);
+      } else if (copilotError?.kind === "unknown_model") {
+        const unknown = error as { model: string; available: string[] };
+        outputEl.textContent = `Copilot does not offer ${unknown.model}. Available: ${unknown.available.join(", ")}`;
+        void loadModels(true);
+      } else if (copilotError?.kind === "budget_exceeded") {
+        const budget = error as { used: number; limit: number };
+        outputEl.textContent = `Monthly budget of ${budget.limit} premium requests reached (${budget.used} used). Pick a free model or raise the limit.`;
+      } else if (copilotError?.kind === "timeout") {
+        const timeout = error as { seconds: number; idle: boolean };
+        outputEl.textContent = timeout.idle
+          ? `Copilot produced no output for ${timeout.seconds}s and was stopped.`
+          : `Copilot did not finish within ${timeout.seconds}s and was stopped.`;
+      } else if (copilotError?.kind) {
+        outputEl.textContent = copilotError.message ?? "Unknown error";
+      } else {
+        outputEl.textContent =
+          error instanceof Error
+            ? error.message
+            : String(error ?? "Unknown error");
+      }
     } finally {
+      unlistenOutput();
+      conversationStored = true;
+      void refreshUsage();
+      activeRequestId = null;
       isRunning = false;
       inputEl.disabled = false;
       if (modelSelect) modelSelect.disabled = false;
       if (sendButton) {
         sendButton.disabled = false;
         sendButton.classList.remove("is-loading");
+        sendButton.setAttribute("aria-label", "Send");
+        sendButton.removeAttribute("title");
       }
-      contextPath = null;
-      contextName = null;
-      if (fileContextEl) {
-        fileContextEl.textContent = "";
-        fileContextEl.title = "";
-        fileContextEl.classList.add("is-hidden");
-      }
+      contextPaths = [];
+      renderContext();
     }
   });
 
   authButton?.addEventListener("click", async () => {
     if (statusTimer) window.clearTimeout(statusTimer);
+    if (activeLoginId) {
+      authStatusEl.textContent = "Cancelling login...";
+      await invoke("cancel_github_login").catch(() => undefined);
+      return;
+    }
     if (hasToken) {
       authStatusEl.textContent = "Logging out...";
       try {
@@ -297,12 +630,15 @@ window.addEventListener("DOMContentLoaded", () => {
 
     authStatusEl.textContent = "Opening GitHub login...";
     try {
-      const { auth_url, user_code } = await invoke<{
+      const { login_id, auth_url, user_code } = await invoke<{
+        login_id: string;
         auth_url: string;
         user_code: string;
         expires_in: number;
         interval: number;
       }>("start_github_login");
+      activeLoginId = login_id;
+      loginUserCode = user_code;
       await openUrl(auth_url);
       authStatusEl.textContent = `Enter code ${user_code} in the browser if prompted.`;
       if (statusTimer) {
@@ -317,25 +653,223 @@ window.addEventListener("DOMContentLoaded", () => {
     }
   });
 
+  accountSelect?.addEventListener("change", async () => {
+    try {
+      await invoke("switch_account", { login: accountSelect.value });
+      authStatusEl.textContent = `Using ${accountSelect.value}.`;
+      await updateTokenStatus();
+    } catch (error) {
+      authStatusEl.textContent = String(error ?? "Switch failed.");
+    }
+  });
+
+  type PromptTemplate = {
+    id: string;
+    name: string;
+    body: string;
+    default_model: string | null;
+    context_path: string | null;
+    variables: string[];
+  };
+  let templates: PromptTemplate[] = [];
+  const SAVE_TEMPLATE = "__save__";
+
+  const hideTemplateVars = () => {
+    if (!templateVarsEl) return;
+    templateVarsEl.replaceChildren();
+    templateVarsEl.classList.add("is-hidden");
+  };
+
+  const loadTemplates = async () => {
+    if (!templateSelect) return;
+    try {
+      templates = await invoke<PromptTemplate[]>("list_templates");
+    } catch {
+      templates = [];
+    }
+    const placeholder = document.createElement("option");
+    placeholder.value = "";
+    placeholder.textContent = "Templates";
+    const saveOption = document.createElement("option");
+    saveOption.value = SAVE_TEMPLATE;
+    saveOption.textContent = "Save prompt as template";
+    templateSelect.replaceChildren(
+      placeholder,
+      ...templates.map((template) => {
+        const option = document.createElement("option");
+        option.value = template.id;
+        option.textContent = template.name;
+        option.title = template.body;
+        return option;
+      }),
+      saveOption,
+    );
+  };
+
+  // Fills the message box from the template, taking the model and context
+  // file it names.
+  const useTemplate = async (
+    template: PromptTemplate,
+    vars: Record<string, string>,
+  ) => {
+    if (!inputEl) return;
+    try {
+      const rendered = await invoke<{
+        prompt: string;
+        model: string | null;
+        context_paths: string[];
+      }>("render_template", { id: template.id, vars });
+      inputEl.value = rendered.prompt;
+      if (rendered.model && modelSelect) {
+        const offered = [...modelSelect.options].some(
+          (option) => option.value === rendered.model,
+        );
+        if (offered) modelSelect.value = rendered.model;
+      }
+      addContextPaths(rendered.context_paths);
+      hideTemplateVars();
+      inputEl.focus();
+    } catch (error) {
+      const templateError = error as {
+        kind?: string;
+        missing?: string[];
+        message?: string;
+      };
+      authStatusEl.textContent =
+        templateError?.kind === "missing_variables"
+          ? `Fill in: ${templateError.missing?.join(", ")}`
+          : templateError?.kind === "context_missing"
+            ? "The template's context file no longer exists."
+            : (templateError?.message ?? String(error));
+    }
+  };
+
+  const showTemplateVars = (template: PromptTemplate) => {
+    if (!templateVarsEl) return;
+    const inputs = template.variables.map((name) => {
+      const field = document.createElement("input");
+      field.type = "text";
+      field.placeholder = name;
+      field.setAttribute("aria-label", name);
+      field.dataset.variable = name;
+      return field;
+    });
+    const useButton = document.createElement("button");
+    useButton.type = "button";
+    useButton.classList.add("footer-button");
+    useButton.textContent = "Use";
+    useButton.addEventListener("click", () => {
+      const vars: Record<string, string> = {};
+      for (const field of inputs) vars[field.dataset.variable ?? ""] = field.value;
+      void useTemplate(template, vars);
+    });
+    const deleteButton = document.createElement("button");
+    deleteButton.type = "button";
+    deleteButton.classList.add("footer-button");
+    deleteButton.textContent = "Delete";
+    deleteButton.addEventListener("click", async () => {
+      try {
+        await invoke("delete_template", { id: template.id });
+        hideTemplateVars();
+        await loadTemplates();
+      } catch (error) {
+        authStatusEl.textContent = String(error ?? "Delete failed.");
+      }
+    });
+    templateVarsEl.replaceChildren(...inputs, useButton, deleteButton);
+    templateVarsEl.classList.remove("is-hidden");
+    inputs[0]?.focus();
+  };
+
+  templateSelect?.addEventListener("change", async () => {
+    const value = templateSelect.value;
+    templateSelect.value = "";
+    if (value === SAVE_TEMPLATE) {
+      const body = inputEl?.value.trim() ?? "";
+      if (!body) {
+        authStatusEl.textContent =
+          "Type a prompt first, using {{name}} for the parts that change.";
+        return;
+      }
+      try {
+        const created = await invoke<PromptTemplate>("create_template", {
+          template: {
+            name: body.split("\n")[0].slice(0, 40),
+            body,
+            defaultModel: modelSelect?.value || undefined,
+          },
+        });
+        authStatusEl.textContent = `Saved template "${created.name}".`;
+        await loadTemplates();
+      } catch (error) {
+        const templateError = error as { message?: string };
+        authStatusEl.textContent =
+          templateError?.message ?? String(error ?? "Save failed.");
+      }
+      return;
+    }
+    const template = templates.find((entry) => entry.id === value);
+    if (template) {
+      showTemplateVars(template);
+    } else {
+      hideTemplateVars();
+    }
+  });
+
+  const refreshUsage = async () => {
+    if (!billingButton) return;
+    try {
+      const summary = await invoke<{
+        month: string;
+        premium_requests: number;
+        budget: { soft_limit: number | null; hard_limit: number | null };
+        budget_state: "ok" | "warning" | "exceeded";
+      }>("get_usage_summary");
+      const limit = summary.budget.hard_limit ?? summary.budget.soft_limit;
+      const used = Math.round(summary.premium_requests * 100) / 100;
+      const label =
+        limit === null
+          ? `Usage: ${used} premium requests in ${summary.month}`
+          : `Usage: ${used} of ${limit} premium requests in ${summary.month}`;
+      billingButton.setAttribute("title", label);
+      billingButton.classList.toggle(
+        "is-over-budget",
+        summary.budget_state !== "ok",
+      );
+    } catch {
+      // The ledger is informational; keep the plain button.
+    }
+  };
+
   billingButton?.addEventListener("click", async () => {
     await openUrl(
       "https://github.com/settings/billing/premium_requests_usage?",
     );
   });
 
+  const addContextPaths = (selected: string | string[] | null) => {
+    if (!selected) return;
+    const paths = Array.isArray(selected) ? selected : [selected];
+    contextPaths = [...new Set([...contextPaths, ...paths])];
+    renderContext();
+  };
+
   fileButton?.addEventListener("click", async () => {
-    const selected = await open({
-      multiple: false,
-      directory: false,
-    });
-    if (typeof selected !== "string") return;
-    contextPath = selected;
-    contextName = selected.split(/[\\/]/).pop() ?? selected;
-    if (fileContextEl) {
-      fileContextEl.textContent = `Context: ${contextName}`;
-      fileContextEl.title = selected;
-      fileContextEl.classList.remove("is-hidden");
-    }
+    addContextPaths(
+      await open({
+        multiple: true,
+        directory: false,
+      }),
+    );
+  });
+
+  folderButton?.addEventListener("click", async () => {
+    addContextPaths(
+      await open({
+        multiple: true,
+        directory: true,
+      }),
+    );
   });
 
   const refreshCopilotStatus = async () => {
@@ -344,7 +878,12 @@ window.addEventListener("DOMContentLoaded", () => {
         installed: boolean;
         version: string | null;
         path: string | null;
+        rule: string | null;
+        candidates: { path: string; rule: string; found: boolean }[];
       }>("get_copilot_status");
+      versionEl.title = status.path
+        ? `${status.path} (found by ${status.rule?.replace("_", " ")})`
+        : `Looked in:\n${status.candidates.map((c) => c.path).join("\n")}`;
       if (status.installed) {
         const match = status.version?.match(/\d+\.\d+\.\d+/);
         versionEl.textContent = match
@@ -355,20 +894,94 @@ window.addEventListener("DOMContentLoaded", () => {
         versionEl.textContent = "copilot not installed";
         installCopilotButton?.classList.remove("is-hidden");
       }
-    } catch {
+    } catch (error) {
+      if ((error as { kind?: string })?.kind === "timeout") {
+        versionEl.textContent = "copilot not responding";
+        return;
+      }
       versionEl.textContent = "copilot";
       installCopilotButton?.classList.remove("is-hidden");
     }
   };
 
+  void listen<{ changed: string[] }>("settings-changed", (event) => {
+    const changed = new Set(event.payload.changed);
+    if (
+      ["copilot_path", "homebrew_prefixes", "default_model"].some((field) =>
+        changed.has(field),
+      )
+    ) {
+      void refreshCopilotStatus();
+      // A new default should be selected rather than the current choice.
+      void loadModels(!changed.has("default_model"));
+    }
+    if (changed.has("usage_budget")) void refreshUsage();
+    if (changed.has("credential_backend")) void updateTokenStatus();
+  });
+
+  void listen<{ action: string; stream: string; line: string }>(
+    "copilot-install-output",
+    (event) => {
+      authStatusEl.textContent = event.payload.line;
+    },
+  );
+
+  const checkCopilotUpdate = async () => {
+    try {
+      const update = await invoke<{
+        installed: string | null;
+        installed_raw: string | null;
+        latest: string | null;
+        state: "up_to_date" | "update_available" | "unknown_installed_version";
+      }>("check_copilot_update");
+      if (update.state === "unknown_installed_version") {
+        authStatusEl.textContent = `Could not read the installed Copilot CLI version${update.installed_raw ? ` ("${update.installed_raw}")` : ""}. Latest is ${update.latest}.`;
+      }
+      if (update.state !== "up_to_date" && upgradeCopilotButton) {
+        upgradeCopilotButton.textContent = `Update to ${update.latest}`;
+        upgradeCopilotButton.classList.remove("is-hidden");
+      } else {
+        upgradeCopilotButton?.classList.add("is-hidden");
+      }
+    } catch {
+      // The package manager may be missing or offline; skip the check.
+      upgradeCopilotButton?.classList.add("is-hidden");
+    }
+  };
+
+  upgradeCopilotButton?.addEventListener("click", async () => {
+    upgradeCopilotButton.disabled = true;
+    authStatusEl.textContent = "Updating Copilot CLI...";
+    try {
+      const result = await invoke<{ success: boolean; message: string }>(
+        "upgrade_copilot_cli",
+      );
+      authStatusEl.textContent = result.message;
+      await refreshCopilotStatus();
+      await loadModels(true);
+      await checkCopilotUpdate();
+    } catch (error) {
+      authStatusEl.textContent = String(error ?? "Copilot update failed.");
+    } finally {
+      upgradeCopilotButton.disabled = false;
+    }
+  });
+
   installCopilotButton?.addEventListener("click", async () => {
     installCopilotButton.disabled = true;
     authStatusEl.textContent = "Installing Copilot CLI...";
     try {
-      const message = await invoke<string>("install_copilot_cli");
-      authStatusEl.textContent = message;
+      const result = await invoke<{
+        success: boolean;
+        exit_code: number | null;
+        message: string;
+      }>("install_copilot_cli");
+      authStatusEl.textContent = result.message;
       await refreshCopilotStatus();
-      if (message.toLowerCase().includes("installed via winget")) {
+      if (result.exit_code === 0) void loadModels(true);
+      // A clean install the app still cannot see usually needs a restart
+      // to pick up the new PATH.
+      if (result.exit_code === 0 && !result.success) {
         setReloadVisible(true);
       }
     } catch (error) {
@@ -381,6 +994,100 @@ window.addEventListener("DOMContentLoaded", () => {
     }
   });
 
+  diagnosticsButton?.addEventListener("click", async () => {
+    const path = await save({
+      defaultPath: "ghc-diagnostics.txt",
+      filters: [{ name: "Text", extensions: ["txt"] }],
+    });
+    if (!path) return;
+    try {
+      const written = await invoke<string>("collect_diagnostics", { path });
+      authStatusEl.textContent = `Diagnostics saved to ${written}.`;
+    } catch (error) {
+      authStatusEl.textContent = String(error ?? "Diagnostics failed.");
+    }
+  });
+
+  const exportFilters: Record<string, { name: string; extension: string }> = {
+    markdown: { name: "Markdown", extension: "md" },
+    html: { name: "HTML", extension: "html" },
+    print_html: { name: "HTML for PDF", extension: "html" },
+    json: { name: "JSON", extension: "json" },
+  };
+
+  exportButton?.addEventListener("click", async () => {
+    const format = exportFormatSelect?.value ?? "markdown";
+    const filter = exportFilters[format] ?? exportFilters.markdown;
+    const path = await save({
+      defaultPath: `ghc-conversation.${filter.extension}`,
+      filters: [{ name: filter.name, extensions: [filter.extension] }],
+    });
+    if (!path) return;
+    try {
+      const written = await invoke<string>("export_conversation", {
+        id: conversationId,
+        format,
+        path,
+      });
+      authStatusEl.textContent = `Conversation exported to ${written}.`;
+    } catch (error) {
+      authStatusEl.textContent = String(error ?? "Export failed.");
+    }
+  });
+
+  newConversationButton?.addEventListener("click", () => {
+    if (isRunning) return;
+    startConversation();
+    authStatusEl.textContent = "Started a new conversation.";
+  });
+
+  deleteConversationButton?.addEventListener("click", async () => {
+    if (isRunning) return;
+    try {
+      // Nothing is stored until the first turn finishes.
+      if (conversationStored) {
+        await invoke("delete_conversation", { id: conversationId });
+      }
+      startConversation();
+      authStatusEl.textContent = "Conversation deleted.";
+    } catch (error) {
+      authStatusEl.textContent = String(error ?? "Delete failed.");
+    }
+  });
+
+  importButton?.addEventListener("click", async () => {
+    if (isRunning) return;
+    const path = await open({
+      multiple: false,
+      filters: [{ name: "ghc export", extensions: ["json", "md"] }],
+    });
+    if (!path) return;
+    try {
+      const result = await invoke<{
+        status: "created" | "merged" | "unchanged";
+        turns_added: number;
+        conversation: { id: string; turns: Turn[] };
+      }>("import_conversation", { path });
+      // Continue the imported thread with the next prompt.
+      startConversation();
+      conversationId = result.conversation.id;
+      conversationStored = true;
+      for (const turn of result.conversation.turns) {
+        if (turn.status !== "success") continue;
+        conversationTurns += 1;
+        if (turn.output.trim()) await appendHistory(turn.prompt, turn.output);
+      }
+      renderConversationStatus();
+      setHistoryVisible(true);
+      authStatusEl.textContent =
+        result.status === "unchanged"
+          ? "Conversation was already imported."
+          : `Imported ${result.turns_added} turn(s).`;
+    } catch (error) {
+      authStatusEl.textContent = String(error ?? "Import failed.");
+    }
+  });
+
   reloadAppButton?.addEventListener("click", () => {
     window.location.reload();
   });
@@ -388,6 +1095,11 @@ window.addEventListener("DOMContentLoaded", () => {
   void (async () => {
     await updateTokenStatus();
     await refreshCopilotStatus();
+    await loadModels();
+    await loadTemplates();
+    await loadHistory();
+    void refreshUsage();
+    void checkCopilotUpdate();
   })();
 
   copyButton.addEventListener("click", async () => {
@@ -423,4 +1135,88 @@ window.addEventListener("DOMContentLoaded", () => {
   historyToggle.addEventListener("click", () => {
     setHistoryVisible(historyEl.hidden);
   });
+
+  type SearchHit = {
+    conversation_id: string;
+    turn_id: string;
+    title: string;
+    model: string;
+    started_at: number;
+    field: "prompt" | "output";
+    snippet: string;
+    highlights: [number, number][];
+  };
+
+  const renderSearchHit = (hit: SearchHit) => {
+    const item = document.createElement("article");
+    item.classList.add("history-item", "history-hit");
+    const titleEl = document.createElement("p");
+    titleEl.classList.add("history-prompt");
+    titleEl.textContent = `${hit.title} · ${hit.model} · ${new Date(
+      hit.started_at,
+    ).toLocaleString()}`;
+    const snippetEl = document.createElement("p");
+    let cursor = 0;
+    for (const [start, end] of hit.highlights) {
+      snippetEl.append(hit.snippet.slice(cursor, start));
+      const mark = document.createElement("mark");
+      mark.textContent = hit.snippet.slice(start, end);
+      snippetEl.append(mark);
+      cursor = end;
+    }
+    snippetEl.append(hit.snippet.slice(cursor));
+    item.append(titleEl, snippetEl);
+    item.title = "Continue from this turn";
+    item.addEventListener("click", async () => {
+      if (isRunning) return;
+      try {
+        const fork = await invoke<{ id: string; turns: Turn[] }>(
+          "fork_conversation",
+          { conversationId: hit.conversation_id, turnId: hit.turn_id },
+        );
+        startConversation();
+        conversationId = fork.id;
+        conversationStored = true;
+        for (const turn of fork.turns) {
+          if (turn.status !== "success") continue;
+          conversationTurns += 1;
+          if (turn.output.trim()) await appendHistory(turn.prompt, turn.output);
+        }
+        renderConversationStatus();
+        if (historySearchEl) historySearchEl.value = "";
+        setSearchResultsVisible(false);
+        historyEl.hidden = false;
+        if (statusTimer) window.clearTimeout(statusTimer);
+        authStatusEl.textContent = `Continuing from "${hit.title}".`;
+        statusTimer = window.setTimeout(() => {
+          authStatusEl.textContent = "";
+        }, 10_000);
+      } catch (error) {
+        authStatusEl.textContent = String(error ?? "Fork failed.");
+      }
+    });
+    return item;
+  };
+
+  let searchTimer: number | undefined;
+  historySearchEl?.addEventListener("input", () => {
+    if (searchTimer) window.clearTimeout(searchTimer);
+    searchTimer = window.setTimeout(async () => {
+      const query = historySearchEl.value.trim();
+      if (!query || !historyResultsEl) {
+        setSearchResultsVisible(false);
+        historyEl.hidden = false;
+        return;
+      }
+      try {
+        const hits = await invoke<SearchHit[]>("search_history", { query });
+        historyResultsEl.replaceChildren(...hits.map(renderSearchHit));
+        historyEl.hidden = true;
+        setSearchResultsVisible(true);
+      } catch {
+        setSearchResultsVisible(false);
+        historyEl.hidden = false;
+      }
+    }, 200);
+  });
 });